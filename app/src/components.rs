@@ -1,2 +1,3 @@
 pub mod background;
 pub mod navigation;
+pub mod responsive_image;
@@ -1,6 +1,8 @@
 use leptos::{ev, prelude::*};
 use leptos_router::components::A;
 
+use crate::components::responsive_image::ResponsiveImage;
+
 #[island]
 pub fn NavigationBar() -> impl IntoView {
     let (is_mobile_open, set_mobile_open) = signal(false);
@@ -22,7 +24,7 @@ pub fn NavigationBar() -> impl IntoView {
                     // Logo
                     <div class="flex-shrink-0">
                         <A href="/">
-                           <image src="/logo.png" class="flex h-10 w-10 object-contain" />
+                           <ResponsiveImage src="/logo.png" alt="Frans Ramirez Neyra" width=40 height=40 class="flex h-10 w-10 object-contain"/>
                         </A>
                     </div>
 
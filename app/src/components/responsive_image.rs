@@ -0,0 +1,30 @@
+use leptos::prelude::*;
+
+use crate::rendering::images::{variant_url, ImageFormat};
+
+/// Fixed-size local image served through the image pipeline, with 1x/2x
+/// AVIF and WebP variants and the original as fallback.
+#[component]
+pub fn ResponsiveImage(
+    #[prop(into)] src: String,
+    #[prop(into)] alt: String,
+    width: u32,
+    height: u32,
+    #[prop(into, optional)] class: String,
+) -> impl IntoView {
+    let density_srcset = |format: ImageFormat| {
+        format!(
+            "{} 1x, {} 2x",
+            variant_url(&src, width, format),
+            variant_url(&src, width * 2, format)
+        )
+    };
+
+    view! {
+        <picture>
+            <source type=ImageFormat::Avif.mime_type() srcset=density_srcset(ImageFormat::Avif)/>
+            <source type=ImageFormat::Webp.mime_type() srcset=density_srcset(ImageFormat::Webp)/>
+            <img src=src.clone() alt=alt width=width height=height class=class decoding="async"/>
+        </picture>
+    }
+}
@@ -0,0 +1,23 @@
+//! The server adds the Content-Security-Policy header once a page is
+//! rendered. Leptos picks the nonce of its inline scripts during rendering,
+//! so the page hands it over through a response header.
+
+/// Response header carrying the nonce from the app to the CSP middleware,
+/// which removes it before the response leaves the server.
+pub const NONCE_HEADER: &str = "x-csp-nonce";
+
+/// Pass the nonce of the current render to the CSP middleware.
+#[cfg(feature = "ssr")]
+pub fn expose_nonce() {
+    use http::{HeaderName, HeaderValue};
+    use leptos::nonce::use_nonce;
+    use leptos::prelude::use_context;
+    use leptos_axum::ResponseOptions;
+
+    let (Some(nonce), Some(response)) = (use_nonce(), use_context::<ResponseOptions>()) else {
+        return;
+    };
+    if let Ok(value) = HeaderValue::from_str(&nonce) {
+        response.insert_header(HeaderName::from_static(NONCE_HEADER), value);
+    }
+}
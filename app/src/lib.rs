@@ -1,29 +1,45 @@
 use leptos::prelude::*;
-use leptos_meta::{provide_meta_context, Meta, MetaTags, Stylesheet, Title};
+use leptos_meta::{provide_meta_context, HashedStylesheet, MetaTags, Title};
 use leptos_router::{
     components::{Route, Router, Routes},
     path,
 };
 
 mod components;
+pub mod csp;
 mod pages;
-mod posts;
+pub mod posts;
+pub mod rendering;
+pub mod seo;
+pub mod site;
 
 use components::background::Background;
 
 use crate::{
     components::navigation::NavigationBar,
-    pages::{blog::BlogPage, blog_post::BlogPost, home::HomePage},
+    pages::{
+        blog::BlogPage,
+        blog_post::BlogPost,
+        error::{NotFound, ServerError},
+        home::HomePage,
+    },
 };
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
+    // A fresh nonce per render, for the inline scripts and the CSP header
+    #[cfg(feature = "ssr")]
+    {
+        leptos::nonce::provide_nonce();
+        csp::expose_nonce();
+    }
+
     view! {
         <!DOCTYPE html>
         <html lang="en">
             <head>
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
-                <meta name="description" content="Expert Fullstack Software Engineer specializing in modern web technologies"/>
+                <HashedStylesheet id="leptos" options=options.clone()/>
                 <AutoReload options=options.clone()/>
                 <HydrationScripts options islands=true/>
                 <MetaTags/>
@@ -41,21 +57,21 @@ pub fn App() -> impl IntoView {
     provide_meta_context();
 
     view! {
-        <Stylesheet id="leptos" href="/pkg/webpage.css"/>
-        // sets the document title
+        // default document title, routes set their own through `Seo`
         <Title text="Frans Ramirez Neyra - Software Engineer"/>
 
-        <Meta name="description" content="Expert Fullstack Software Engineer specializing in modern web technologies"/>
         // content for this welcome page
         <Router>
             <Background />
             <NavigationBar/>
             <main>
-                <Routes fallback=|| "Page not found.".into_view()>
-                        <Route path=path!("/") view=HomePage/>
-                        <Route path=path!("/blog") view=BlogPage/>
-                        <Route path=path!("/blog/:slug") view=BlogPost/>
-                </Routes>
+                <ErrorBoundary fallback=|_| view! { <ServerError/> }>
+                    <Routes fallback=|| view! { <NotFound/> }>
+                            <Route path=path!("/") view=HomePage/>
+                            <Route path=path!("/blog") view=BlogPage/>
+                            <Route path=path!("/blog/:slug") view=BlogPost/>
+                    </Routes>
+                </ErrorBoundary>
             </main>
         </Router>
     }
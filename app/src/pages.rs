@@ -1,3 +1,4 @@
 pub mod blog;
 pub mod blog_post;
+pub mod error;
 pub mod home;
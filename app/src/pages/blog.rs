@@ -1,11 +1,19 @@
+use std::collections::BTreeMap;
+
 use leptos::prelude::*;
 use leptos_router::components::A;
 
-use crate::posts::{get_posts, BlogPostData};
+use crate::posts::{posts_with_renames, renamed_posts, BlogPostData};
+use crate::seo::Seo;
 
 #[component]
 pub fn BlogPage() -> impl IntoView {
     view! {
+        <Seo
+            title="Engineering Log | Frans Ramirez Neyra"
+            description="Insights, discoveries, and technical adventures from the frontier of software engineering"
+            path="/blog"
+        />
         <div class="pt-32 pb-20 px-4">
             <div class="max-w-6xl mx-auto">
                 <div class="text-center mb-16">
@@ -17,7 +25,7 @@ pub fn BlogPage() -> impl IntoView {
                     </p>
                 </div>
 
-                <BlogGrid/>
+                <BlogGrid renamed=renamed_posts()/>
             </div>
         </div>
     }
@@ -25,11 +33,11 @@ pub fn BlogPage() -> impl IntoView {
 
 // Island component for interactive blog functionality
 #[island]
-fn BlogGrid() -> impl IntoView {
+fn BlogGrid(renamed: BTreeMap<String, String>) -> impl IntoView {
     let (search_term, set_search_term) = signal(String::new());
     let (selected_category, set_selected_category) = signal("All".to_string());
 
-    let blog_posts = get_posts();
+    let blog_posts = posts_with_renames(&renamed);
 
     let categories = vec!["All", "Rust", "Cloud", "Machine Learning", "TypeScript"];
 
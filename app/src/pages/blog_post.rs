@@ -1,8 +1,13 @@
+use http::StatusCode;
 use leptos::prelude::*;
 use leptos_router::components::A;
 use leptos_router::hooks::use_params_map;
 
-use crate::posts::{get_post, BlogPostData};
+use crate::pages::error::ErrorPage;
+use crate::posts::{find_post, BlogPostData, PostLookup};
+use crate::rendering::render_markdown;
+use crate::seo::{blog_posting, PageKind, Seo};
+use crate::site::{og_image_path, OG_IMAGE_SIZE};
 
 #[component]
 pub fn BlogPost() -> impl IntoView {
@@ -15,53 +20,71 @@ pub fn BlogPost() -> impl IntoView {
         <div class="pt-32 pb-20 px-4">
             <div class="max-w-4xl mx-auto">
                 {move || {
-                    if let Some(post) = get_post(&slug()) {
-                        let iternal_post = post.clone();
-                        view! {
-                            <article>
-                                <header class="mb-12">
-                                    <div class="flex items-center gap-4 mb-6">
-                                        <A href="/blog">
-                                <p class="flex items-center text-cyan-400 hover:text-purple-400 transition-colors">
-                                            <svg class="w-4 h-4 mr-2" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                                                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7"/>
-                                            </svg>
-                                            "Back to Blog"
-                                </p>
-                                        </A>
-                                    </div>
+                    match find_post(&slug()) {
+                        PostLookup::Found(post) => {
+                            let iternal_post = post.clone();
+                            view! {
+                                <Seo
+                                    title=format!("{} | Frans Ramirez Neyra", post.title)
+                                    description=post.excerpt.clone()
+                                    path=format!("/blog/{}", post.slug)
+                                    kind=PageKind::Article
+                                    image=og_image_path(&post.slug)
+                                    image_size=OG_IMAGE_SIZE
+                                    published=post.date.clone()
+                                    json_ld=blog_posting(&post)
+                                />
+                                <article>
+                                    <header class="mb-12">
+                                        <div class="flex items-center gap-4 mb-6">
+                                            <A href="/blog">
+                                    <p class="flex items-center text-cyan-400 hover:text-purple-400 transition-colors">
+                                                <svg class="w-4 h-4 mr-2" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7"/>
+                                                </svg>
+                                                "Back to Blog"
+                                    </p>
+                                            </A>
+                                        </div>
 
-                                    <div class="flex items-center gap-4 mb-6">
-                                        <span class="px-3 py-1 bg-gradient-to-r from-purple-500/20 to-cyan-500/20 rounded-full text-sm border border-purple-500/30 text-cyan-400">
-                                            {post.category}
-                                        </span>
-                                        <span class="text-gray-400">{post.date}</span>
-                                        <span class="text-gray-400">-</span>
-                                        <span class="text-gray-400">{post.read_time}</span>
-                                    </div>
+                                        <div class="flex items-center gap-4 mb-6">
+                                            <span class="px-3 py-1 bg-gradient-to-r from-purple-500/20 to-cyan-500/20 rounded-full text-sm border border-purple-500/30 text-cyan-400">
+                                                {post.category}
+                                            </span>
+                                            <span class="text-gray-400">{post.date}</span>
+                                            <span class="text-gray-400">-</span>
+                                            <span class="text-gray-400">{post.read_time}</span>
+                                        </div>
 
-                                    <h1 class="courier text-4xl md:text-5xl font-bold text-transparent bg-clip-text bg-gradient-to-r from-cyan-400 to-purple-500 mb-6">
-                                        {post.title}
-                                    </h1>
-                                </header>
+                                        <h1 class="courier text-4xl md:text-5xl font-bold text-transparent bg-clip-text bg-gradient-to-r from-cyan-400 to-purple-500 mb-6">
+                                            {post.title}
+                                        </h1>
+                                    </header>
 
-                                <div class="prose prose-invert prose-lg max-w-none">
-                                    <BlogContent post=iternal_post/>
-                                </div>
-                            </article>
-                        }.into_any()
-                    } else {
-                        view! {
-                            <div class="text-center">
-                                <h1 class="courier text-4xl font-bold text-red-400 mb-4">"404 - Post Not Found"</h1>
-                                <p class="text-gray-300 mb-8">"The blog post you're looking for doesn't exist."</p>
-                                <A href="/blog" >
-                                <p class="inline-flex items-center px-6 py-3 bg-gradient-to-r from-purple-600 to-cyan-600 rounded-full hover:from-purple-500 hover:to-cyan-500 transition-all duration-300 font-semibold">
-                                    "Back to Blog"
-                                </p>
-                                </A>
-                            </div>
-                        }.into_any()
+                                    <div class="prose prose-invert prose-lg max-w-none">
+                                        <BlogContent post=iternal_post/>
+                                    </div>
+                                </article>
+                            }.into_any()
+                        }
+                        PostLookup::Removed => view! {
+                            <ErrorPage
+                                status=StatusCode::GONE
+                                title="Post Removed"
+                                message="This blog post has been permanently removed."
+                                back_href="/blog"
+                                back_label="Back to Blog"
+                            />
+                        }.into_any(),
+                        PostLookup::Missing => view! {
+                            <ErrorPage
+                                status=StatusCode::NOT_FOUND
+                                title="Post Not Found"
+                                message="The blog post you're looking for doesn't exist."
+                                back_href="/blog"
+                                back_label="Back to Blog"
+                            />
+                        }.into_any(),
                     }
                 }}
             </div>
@@ -71,8 +94,7 @@ pub fn BlogPost() -> impl IntoView {
 
 #[component]
 fn BlogContent(post: BlogPostData) -> impl IntoView {
-    let info = markdown::to_html_with_options(&post.content, &markdown::Options::gfm())
-        .unwrap_or_default();
+    let info = render_markdown(&post.content);
     view! {
         <div class="markdown-body card-hover rounded-xl p-8 space-y-6" inner_html=info >
                     </div>
@@ -0,0 +1,66 @@
+use http::StatusCode;
+use leptos::prelude::*;
+use leptos_meta::{Meta, Title};
+use leptos_router::components::A;
+
+/// Styled error message. On the server it also sets the HTTP status of the
+/// response, so crawlers and caches see the real outcome.
+#[component]
+pub fn ErrorPage(
+    status: StatusCode,
+    #[prop(into)] title: String,
+    #[prop(into)] message: String,
+    #[prop(into, default = "/".to_string())] back_href: String,
+    #[prop(into, default = "Back Home".to_string())] back_label: String,
+) -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+        response.set_status(status);
+    }
+
+    view! {
+        <Title text=format!("{} | Frans Ramirez Neyra", title)/>
+        <Meta name="robots" content="noindex"/>
+        <div class="text-center">
+            <h1 class="courier text-4xl font-bold text-red-400 mb-4">
+                {format!("{} - {}", status.as_u16(), title)}
+            </h1>
+            <p class="text-gray-300 mb-8">{message}</p>
+            <A href=back_href>
+                <p class="inline-flex items-center px-6 py-3 bg-gradient-to-r from-purple-600 to-cyan-600 rounded-full hover:from-purple-500 hover:to-cyan-500 transition-all duration-300 font-semibold">
+                    {back_label}
+                </p>
+            </A>
+        </div>
+    }
+}
+
+#[component]
+pub fn NotFound() -> impl IntoView {
+    view! {
+        <div class="pt-32 pb-20 px-4">
+            <div class="max-w-4xl mx-auto">
+                <ErrorPage
+                    status=StatusCode::NOT_FOUND
+                    title="Page Not Found"
+                    message="The page you're looking for doesn't exist."
+                />
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn ServerError() -> impl IntoView {
+    view! {
+        <div class="pt-32 pb-20 px-4">
+            <div class="max-w-4xl mx-auto">
+                <ErrorPage
+                    status=StatusCode::INTERNAL_SERVER_ERROR
+                    title="Something Went Wrong"
+                    message="An unexpected error occurred while loading this page. Please try again later."
+                />
+            </div>
+        </div>
+    }
+}
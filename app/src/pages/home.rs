@@ -1,9 +1,20 @@
 use leptos::prelude::*;
+use serde_json::{json, Value};
+
+use crate::seo::{author, PageKind, Seo};
+use crate::site::absolute_url;
 
 /// Renders the home page of your application.
 #[component]
 pub fn HomePage() -> impl IntoView {
     view! {
+        <Seo
+            title="Frans Ramirez Neyra - Software Engineer"
+            description="Expert Fullstack Software Engineer specializing in modern web technologies"
+            path="/"
+            kind=PageKind::Profile
+            json_ld=person()
+        />
         <div class="min-h-screen text-white">
             <main class="relative">
                 <HeroSection/>
@@ -18,6 +29,52 @@ pub fn HomePage() -> impl IntoView {
     }
 }
 
+/// Schema.org `Person` for the author. The current role goes under
+/// `worksFor`, past ones under `alumniOf` next to the university, each as an
+/// `EmployeeRole` carrying the dates.
+fn person() -> Value {
+    let role = |experience: &Experience, property: &str| {
+        let mut role = json!({
+            "@type": "EmployeeRole",
+            "roleName": experience.position,
+            "startDate": experience.start_date,
+            "endDate": experience.end_date,
+            "description": experience.description,
+        });
+        role[property] = json!({ "@type": "Organization", "name": experience.company });
+        role
+    };
+    let (current, past): (Vec<&Experience>, Vec<&Experience>) = EXPERIENCE
+        .iter()
+        .partition(|experience| experience.end_date.is_none());
+
+    let mut alumni_of = vec![json!({
+        "@type": "CollegeOrUniversity",
+        "name": "Tecnológico de Monterrey",
+    })];
+    alumni_of.extend(
+        past.into_iter()
+            .map(|experience| role(experience, "alumniOf")),
+    );
+
+    let mut person = author();
+    person["@context"] = json!("https://schema.org");
+    person["jobTitle"] = json!("Software Engineer");
+    person["image"] = json!(absolute_url("/logo.png"));
+    person["address"] = json!({
+        "@type": "PostalAddress",
+        "addressLocality": "Toronto",
+        "addressRegion": "ON",
+        "addressCountry": "CA",
+    });
+    person["alumniOf"] = json!(alumni_of);
+    person["worksFor"] = json!(current
+        .into_iter()
+        .map(|experience| role(experience, "worksFor"))
+        .collect::<Vec<_>>());
+    person
+}
+
 #[component]
 fn HeroSection() -> impl IntoView {
     view! {
@@ -116,6 +173,94 @@ fn SkillCard(title: &'static str, skills: Vec<&'static str>) -> impl IntoView {
     }
 }
 
+/// Work history, shared by the experience section and the `Person`
+/// structured data.
+struct Experience {
+    company: &'static str,
+    position: &'static str,
+    location: &'static str,
+    period: &'static str,
+    /// `YYYY-MM`
+    start_date: &'static str,
+    end_date: Option<&'static str>,
+    description: &'static str,
+}
+
+const EXPERIENCE: [Experience; 8] = [
+    Experience {
+        company: "MyMA",
+        position: "Tech Lead",
+        location: "New York, NY",
+        period: "January 2023 - Present",
+        start_date: "2023-01",
+        end_date: None,
+        description: "Worked as a Full stack engineer to create an art platform to engage artists and art all in one. Developed a Next.js web app using TypeScript and Node, using AWS infrastructure and all the tooling needed for a progressive App.",
+    },
+    Experience {
+        company: "Kanuby",
+        position: "Senior Full-stack Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "November 2022 - March 2023",
+        start_date: "2022-11",
+        end_date: Some("2023-03"),
+        description: "Worked as a Full stack engineer to create a platform to offer Storage services. Developed a Remix.run web app using TypeScript and Node, using Fly.io infrastructure and GitHub CI/CD.",
+    },
+    Experience {
+        company: "Vest, Inc.",
+        position: "Tech Lead",
+        location: "Mexico City, Mexico",
+        period: "February 2021 - October 2022",
+        start_date: "2021-02",
+        end_date: Some("2022-10"),
+        description: "Developed back-end architecture to connect all services on trading and banking. Built services in Golang for performance optimization and Python for fast development. Built all the IAC in Pulumi and AWS using ECS, ElasticLB, RDS, ElasticCache. Worked on React Native features including Authentication with Cognito and real-time Stock pricing.",
+    },
+    Experience {
+        company: "Modus Create",
+        position: "Senior Full-stack Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "August 2020 - February 2021",
+        start_date: "2020-08",
+        end_date: Some("2021-02"),
+        description: "Leveraged Chalice and AWS SDK to build serverless backend and infrastructure for a cloud-based web development platform. Worked with Cloudflare Workers to deploy serverless code globally for Pfizer. Optimized page response times using Cloudflare Cache for Pfizer worldwide pages.",
+    },
+    Experience {
+        company: "Chiper",
+        position: "Full-stack Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "September 2019 - March 2020",
+        start_date: "2019-09",
+        end_date: Some("2020-03"),
+        description: "Developed Retail Management System using LoopBack JS, React JS and MySQL for inventory management. Created Operational System for internal processes and bills payment transaction system for POS using KoaJS, Objection and PostgreSQL.",
+    },
+    Experience {
+        company: "Credijusto/Covalto Bank",
+        position: "Full-stack Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "March 2019 - September 2019",
+        start_date: "2019-03",
+        end_date: Some("2019-09"),
+        description: "Built environment per branch feature on CI/CD pipeline using GitLab and Kubernetes. Added amortization calculator using Django framework. Created React components for Dash framework dashboards. Maintained Compliance blacklisting system in Golang and worked with OroCRM and Symfony.",
+    },
+    Experience {
+        company: "Nure",
+        position: "Full-stack and Machine Learning Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "June 2018 - December 2021",
+        start_date: "2018-06",
+        end_date: Some("2021-12"),
+        description: "Developed Reverse Image Search Engine based on Deep Neural Network (VGG-16 and RESNET) characteristics extraction. Vectorized results and indexed them on Elastic Search. Dockerized solution with Nvidia runtime and orchestration for autoscaling.",
+    },
+    Experience {
+        company: "Connus International",
+        position: "Software Engineer",
+        location: "Mexico City, Mexico",
+        period: "March 2017 - September 2018",
+        start_date: "2017-03",
+        end_date: Some("2018-09"),
+        description: "Worked on LPR software integration using C++, training OpenALPR for Mexican car plates. Implemented AWS face recognition for security solutions. Developed IoT monitoring platform using MERN stack. Adapted ML technologies (Detectron, Fast-RCNN, YOLO) with TensorFlow and Caffe for Object Detection.",
+    },
+];
+
 #[component]
 fn ExperienceSection() -> impl IntoView {
     view! {
@@ -125,62 +270,15 @@ fn ExperienceSection() -> impl IntoView {
                     "MISSION HISTORY"
                 </h2>
                 <div class="space-y-8">
-                    <ExperienceCard
-                        company="MyMA"
-                        position="Tech Lead"
-                        location="New York, NY"
-                        period="January 2023 - Present"
-                        description="Worked as a Full stack engineer to create an art platform to engage artists and art all in one. Developed a Next.js web app using TypeScript and Node, using AWS infrastructure and all the tooling needed for a progressive App."
-                    />
-                    <ExperienceCard
-                        company="Kanuby"
-                        position="Senior Full-stack Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="November 2022 - March 2023"
-                        description="Worked as a Full stack engineer to create a platform to offer Storage services. Developed a Remix.run web app using TypeScript and Node, using Fly.io infrastructure and GitHub CI/CD."
-                    />
-                    <ExperienceCard
-                        company="Vest, Inc."
-                        position="Tech Lead"
-                        location="Mexico City, Mexico"
-                        period="February 2021 - October 2022"
-                        description="Developed back-end architecture to connect all services on trading and banking. Built services in Golang for performance optimization and Python for fast development. Built all the IAC in Pulumi and AWS using ECS, ElasticLB, RDS, ElasticCache. Worked on React Native features including Authentication with Cognito and real-time Stock pricing."
-                    />
-                    <ExperienceCard
-                        company="Modus Create"
-                        position="Senior Full-stack Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="August 2020 - February 2021"
-                        description="Leveraged Chalice and AWS SDK to build serverless backend and infrastructure for a cloud-based web development platform. Worked with Cloudflare Workers to deploy serverless code globally for Pfizer. Optimized page response times using Cloudflare Cache for Pfizer worldwide pages."
-                    />
-                    <ExperienceCard
-                        company="Chiper"
-                        position="Full-stack Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="September 2019 - March 2020"
-                        description="Developed Retail Management System using LoopBack JS, React JS and MySQL for inventory management. Created Operational System for internal processes and bills payment transaction system for POS using KoaJS, Objection and PostgreSQL."
-                    />
-                    <ExperienceCard
-                        company="Credijusto/Covalto Bank"
-                        position="Full-stack Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="March 2019 - September 2019"
-                        description="Built environment per branch feature on CI/CD pipeline using GitLab and Kubernetes. Added amortization calculator using Django framework. Created React components for Dash framework dashboards. Maintained Compliance blacklisting system in Golang and worked with OroCRM and Symfony."
-                    />
-                    <ExperienceCard
-                        company="Nure"
-                        position="Full-stack and Machine Learning Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="June 2018 - December 2021"
-                        description="Developed Reverse Image Search Engine based on Deep Neural Network (VGG-16 and RESNET) characteristics extraction. Vectorized results and indexed them on Elastic Search. Dockerized solution with Nvidia runtime and orchestration for autoscaling."
-                    />
-                    <ExperienceCard
-                        company="Connus International"
-                        position="Software Engineer"
-                        location="Mexico City, Mexico"
-                        period="March 2017 - September 2018"
-                        description="Worked on LPR software integration using C++, training OpenALPR for Mexican car plates. Implemented AWS face recognition for security solutions. Developed IoT monitoring platform using MERN stack. Adapted ML technologies (Detectron, Fast-RCNN, YOLO) with TensorFlow and Caffe for Object Detection."
-                    />
+                    {EXPERIENCE.iter().map(|experience| view! {
+                        <ExperienceCard
+                            company=experience.company
+                            position=experience.position
+                            location=experience.location
+                            period=experience.period
+                            description=experience.description
+                        />
+                    }).collect::<Vec<_>>()}
                 </div>
             </div>
         </section>
@@ -1,3 +1,7 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "ssr")]
+use std::sync::{PoisonError, RwLock};
+
 #[derive(Clone)]
 pub struct BlogPostData {
     pub slug: String,
@@ -5,11 +9,31 @@ pub struct BlogPostData {
     pub excerpt: String,
     pub category: String,
     pub date: String,
+    /// Day of the last edit, `date` until the post is edited.
+    pub updated_at: String,
     pub read_time: String,
     pub content: String,
 }
 
+/// Every post, under its current slug.
 pub fn get_posts() -> Vec<BlogPostData> {
+    posts_with_renames(&renamed_posts())
+}
+
+/// Every post, under the slugs of `renamed` from [`renamed_posts`]. Islands
+/// are handed the renames, the browser doesn't know about them otherwise.
+pub fn posts_with_renames(renamed: &BTreeMap<String, String>) -> Vec<BlogPostData> {
+    let mut posts = written_posts();
+    for post in &mut posts {
+        if let Some(slug) = renamed.get(&post.slug) {
+            post.slug = slug.clone();
+        }
+    }
+    posts
+}
+
+/// Posts with the slug they were written with.
+fn written_posts() -> Vec<BlogPostData> {
     let posts: Vec<BlogPostData> = vec![
         BlogPostData {
             slug: "tanstack-forms-custom-fields".to_string(),
@@ -18,13 +42,155 @@ pub fn get_posts() -> Vec<BlogPostData> {
             content:  include_str!("./posts/tanstack_forms.md").to_string(),
             category: "typescript".to_string(),
             date: "2024-12-15".to_string(),
+            updated_at: "2024-12-15".to_string(),
             read_time: "10 min read".to_string(),
         },
     ];
     posts
 }
 
+/// New slugs of renamed posts, by the slug they were written with. Renames
+/// are made by admins and kept in the database, the server loads them.
+#[cfg(feature = "ssr")]
+static RENAMED_POSTS: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// New slugs of renamed posts, by the slug they were written with.
+pub fn renamed_posts() -> BTreeMap<String, String> {
+    #[cfg(feature = "ssr")]
+    {
+        RENAMED_POSTS
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    #[cfg(not(feature = "ssr"))]
+    BTreeMap::new()
+}
+
+/// Replace the renamed posts, see [`original_slug`]. The [`post_changed`]
+/// listeners hear about the old and new slug of every post whose slug
+/// changed.
+#[cfg(feature = "ssr")]
+pub fn set_renamed_posts(renamed: BTreeMap<String, String>) {
+    let previous = std::mem::replace(
+        &mut *RENAMED_POSTS
+            .write()
+            .unwrap_or_else(PoisonError::into_inner),
+        renamed.clone(),
+    );
+    let before = posts_with_renames(&previous);
+    let after = posts_with_renames(&renamed);
+    for (old, new) in before.iter().zip(&after) {
+        if old.slug != new.slug {
+            post_changed(&old.slug);
+            post_changed(&new.slug);
+        }
+    }
+}
+
+/// The slug the post now at `slug` was written with, which renames are
+/// recorded under.
+#[cfg(feature = "ssr")]
+pub fn original_slug(slug: &str) -> Option<String> {
+    let renamed = RENAMED_POSTS.read().unwrap_or_else(PoisonError::into_inner);
+    written_posts()
+        .into_iter()
+        .map(|post| post.slug)
+        .find(|original| renamed.get(original).unwrap_or(original) == slug)
+}
+
+/// Slugs of posts that were taken down on purpose. They answer with
+/// `410 Gone` instead of `404 Not Found`.
+const REMOVED_POSTS: &[&str] = &[];
+
+pub enum PostLookup {
+    Found(BlogPostData),
+    Removed,
+    Missing,
+}
+
 pub fn get_post(slug: &str) -> Option<BlogPostData> {
     let posts = get_posts();
     posts.iter().find(|post| post.slug == slug).cloned()
 }
+
+pub fn find_post(slug: &str) -> PostLookup {
+    lookup(slug, &get_posts(), REMOVED_POSTS)
+}
+
+/// A removed slug that is published again is found like any other post.
+fn lookup(slug: &str, posts: &[BlogPostData], removed: &[&str]) -> PostLookup {
+    match posts.iter().find(|post| post.slug == slug) {
+        Some(post) => PostLookup::Found(post.clone()),
+        None if removed.contains(&slug) => PostLookup::Removed,
+        None => PostLookup::Missing,
+    }
+}
+
+#[cfg(feature = "ssr")]
+type PostListener = Box<dyn Fn(&str) + Send + Sync>;
+
+#[cfg(feature = "ssr")]
+static POST_LISTENERS: RwLock<Vec<PostListener>> = RwLock::new(Vec::new());
+
+/// Call `listener` with the slug of every post that changes while the
+/// server runs, e.g. to drop cached pages showing it. Posts are compiled in,
+/// so that is an admin renaming one, other edits come with a new build.
+#[cfg(feature = "ssr")]
+pub fn on_post_changed(listener: impl Fn(&str) + Send + Sync + 'static) {
+    POST_LISTENERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Box::new(listener));
+}
+
+/// Tell every [`on_post_changed`] listener that the post `slug` changed.
+#[cfg(feature = "ssr")]
+pub fn post_changed(slug: &str) {
+    let listeners = POST_LISTENERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    for listener in listeners.iter() {
+        listener(slug);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_post() {
+        let posts = get_posts();
+        let slug = posts[0].slug.as_str();
+        assert!(matches!(find_post(slug), PostLookup::Found(post) if post.slug == slug));
+        assert!(matches!(find_post("no-such-post"), PostLookup::Missing));
+
+        let removed = ["taken-down", slug];
+        assert!(matches!(
+            lookup("taken-down", &posts, &removed),
+            PostLookup::Removed
+        ));
+        assert!(matches!(
+            lookup(slug, &posts, &removed),
+            PostLookup::Found(_)
+        ));
+        assert!(matches!(
+            lookup("no-such-post", &posts, &removed),
+            PostLookup::Missing
+        ));
+    }
+
+    #[test]
+    fn test_posts_with_renames() {
+        let original = written_posts()[0].slug.clone();
+        let renamed = BTreeMap::from([
+            (original.clone(), "new-slug".to_string()),
+            ("no-such-post".to_string(), "other-slug".to_string()),
+        ]);
+        let posts = posts_with_renames(&renamed);
+        assert_eq!(posts[0].slug, "new-slug");
+        assert_eq!(posts.len(), written_posts().len());
+        assert!(posts.iter().all(|post| post.slug != original));
+    }
+}
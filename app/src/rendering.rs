@@ -0,0 +1,199 @@
+pub mod directives;
+pub mod images;
+
+use std::sync::OnceLock;
+
+use directives::{parse_leaf, parse_opening, DirectiveRegistry};
+
+/// Markdown pipeline used for blog content: GFM plus the directives
+/// registered in a [`DirectiveRegistry`].
+pub struct MarkdownRenderer {
+    registry: DirectiveRegistry,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self::new(DirectiveRegistry::default())
+    }
+}
+
+impl MarkdownRenderer {
+    pub fn new(registry: DirectiveRegistry) -> Self {
+        Self { registry }
+    }
+
+    pub fn registry(&self) -> &DirectiveRegistry {
+        &self.registry
+    }
+
+    /// Render markdown to HTML. Directive blocks are handed to their
+    /// handlers, everything in between goes through the GFM renderer.
+    pub fn render(&self, source: &str) -> String {
+        let mut html = String::new();
+        let mut pending = String::new();
+        let mut lines = source.lines();
+        let mut fence: Option<String> = None;
+
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim_start();
+
+            // Never look for directives inside fenced code blocks
+            if let Some(marker) = &fence {
+                if trimmed.starts_with(marker.as_str()) {
+                    fence = None;
+                }
+                push_line(&mut pending, line);
+                continue;
+            }
+            if let Some(marker) = fence_marker(trimmed) {
+                fence = Some(marker);
+                push_line(&mut pending, line);
+                continue;
+            }
+
+            let directive = match parse_opening(trimmed) {
+                Some(opening) => Some(opening.with_body(line, collect_container(&mut lines))),
+                None => parse_leaf(trimmed, line),
+            };
+
+            match directive {
+                Some(directive) if self.registry.get(&directive.name).is_some() => {
+                    self.flush(&mut pending, &mut html);
+                    match self.registry.render(&directive, self) {
+                        Ok(rendered) => html.push_str(&rendered),
+                        Err(err) => {
+                            tracing::warn!(directive = %directive.name, "directive failed to render: {err}");
+                            html.push_str(&self.render_plain(&directive.source));
+                        }
+                    }
+                }
+                // Unknown directives are left as regular markdown
+                Some(directive) => pending.push_str(&directive.source),
+                None => push_line(&mut pending, line),
+            }
+        }
+        self.flush(&mut pending, &mut html);
+
+        html
+    }
+
+    fn flush(&self, pending: &mut String, html: &mut String) {
+        if !pending.trim().is_empty() {
+            html.push_str(&self.render_plain(pending));
+        }
+        pending.clear();
+    }
+
+    fn render_plain(&self, source: &str) -> String {
+        let html =
+            markdown::to_html_with_options(source, &markdown::Options::gfm()).unwrap_or_default();
+        images::rewrite_images(&html)
+    }
+}
+
+/// Render markdown with the default directive registry.
+pub fn render_markdown(source: &str) -> String {
+    static RENDERER: OnceLock<MarkdownRenderer> = OnceLock::new();
+    RENDERER
+        .get_or_init(MarkdownRenderer::default)
+        .render(source)
+}
+
+/// Escape text for use in HTML content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn push_line(buffer: &mut String, line: &str) {
+    buffer.push_str(line);
+    buffer.push('\n');
+}
+
+fn fence_marker(line: &str) -> Option<String> {
+    ["```", "~~~"].into_iter().find_map(|marker| {
+        line.starts_with(marker).then(|| {
+            let fence_char = marker.chars().next().unwrap_or('`');
+            line.chars().take_while(|c| *c == fence_char).collect()
+        })
+    })
+}
+
+/// Consume the lines of a container directive up to its closing `:::`,
+/// keeping nested containers intact for the recursive render.
+fn collect_container<'a>(lines: &mut impl Iterator<Item = &'a str>) -> String {
+    let mut body = String::new();
+    let mut depth = 0usize;
+    let mut fence: Option<String> = None;
+
+    for line in lines.by_ref() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = &fence {
+            if trimmed.starts_with(marker.as_str()) {
+                fence = None;
+            }
+        } else if let Some(marker) = fence_marker(trimmed) {
+            fence = Some(marker);
+        } else if parse_opening(trimmed).is_some() {
+            depth += 1;
+        } else if trimmed.trim_end() == ":::" {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+        }
+        push_line(&mut body, line);
+    }
+
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_directive() {
+        let html = render_markdown(":::warning[Careful]\nSome **bold** text\n:::\n");
+        assert!(html.contains(r#"class="callout callout-warning""#));
+        assert!(html.contains("Careful"));
+        assert!(html.contains("<strong>bold</strong>"));
+    }
+
+    #[test]
+    fn test_leaf_directive_attributes() {
+        let html = render_markdown(r#"::figure{src="/logo.png" caption="The logo"}"#);
+        assert!(html.contains(r#"<img src="/logo.png" alt="The logo""#));
+        assert!(html.contains("<figcaption>The logo</figcaption>"));
+    }
+
+    #[test]
+    fn test_directives_ignored_in_code_and_unknown() {
+        let html = render_markdown("```\n:::note\n```\n\n::unknown{a=b}\n");
+        assert!(!html.contains("callout"));
+        assert!(html.contains("::unknown{a=b}"));
+    }
+
+    #[test]
+    fn test_custom_directive() {
+        let mut registry = directives::DirectiveRegistry::new();
+        registry.register(
+            "shout",
+            |d: &directives::Directive, _: &MarkdownRenderer| {
+                Ok(d.label.clone().unwrap_or_default().to_uppercase())
+            },
+        );
+        let html = MarkdownRenderer::new(registry).render("::shout[hello]\n");
+        assert_eq!(html, "HELLO");
+    }
+}
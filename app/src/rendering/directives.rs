@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, HashMap};
+
+use thiserror::Error;
+
+use super::{escape_html, images, MarkdownRenderer};
+
+#[derive(Debug, Error)]
+pub enum DirectiveError {
+    #[error("missing required attribute `{0}`")]
+    MissingAttribute(&'static str),
+
+    #[error("invalid value for attribute `{attribute}`: {reason}")]
+    InvalidAttribute {
+        attribute: &'static str,
+        reason: String,
+    },
+
+    #[error("directive must be used as a {0}")]
+    WrongKind(&'static str),
+}
+
+/// A parsed directive, either a leaf (`::figure{src="/a.png"}`) or a
+/// container (`:::note` ... `:::`) whose body is raw markdown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directive {
+    pub name: String,
+    pub label: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+    pub body: Option<String>,
+    /// Original markdown, used when the directive cannot be rendered.
+    pub source: String,
+}
+
+impl Directive {
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    pub fn required(&self, key: &'static str) -> Result<&str, DirectiveError> {
+        self.attribute(key)
+            .filter(|value| !value.is_empty())
+            .ok_or(DirectiveError::MissingAttribute(key))
+    }
+}
+
+/// Opening line of a container directive, waiting for its body.
+pub struct Opening {
+    name: String,
+    label: Option<String>,
+    attributes: BTreeMap<String, String>,
+}
+
+impl Opening {
+    pub(crate) fn with_body(self, line: &str, body: String) -> Directive {
+        Directive {
+            source: format!("{line}\n{body}:::\n"),
+            name: self.name,
+            label: self.label,
+            attributes: self.attributes,
+            body: Some(body),
+        }
+    }
+}
+
+/// Renders one directive to HTML. Implemented for plain functions and
+/// closures so small directives don't need their own type.
+pub trait DirectiveHandler: Send + Sync {
+    fn render(
+        &self,
+        directive: &Directive,
+        renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError>;
+}
+
+impl<F> DirectiveHandler for F
+where
+    F: Fn(&Directive, &MarkdownRenderer) -> Result<String, DirectiveError> + Send + Sync,
+{
+    fn render(
+        &self,
+        directive: &Directive,
+        renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError> {
+        self(directive, renderer)
+    }
+}
+
+/// Directive name to handler lookup. `Default` registers the built-in
+/// callouts, `figure` and `embed`.
+pub struct DirectiveRegistry {
+    handlers: HashMap<String, Box<dyn DirectiveHandler>>,
+}
+
+impl Default for DirectiveRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register("note", Callout::new("note", "Note"))
+            .register("tip", Callout::new("tip", "Tip"))
+            .register("warning", Callout::new("warning", "Warning"))
+            .register("danger", Callout::new("danger", "Danger"))
+            .register("figure", Figure)
+            .register("embed", Embed);
+        registry
+    }
+}
+
+impl DirectiveRegistry {
+    /// An empty registry, without the built-in directives.
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Register a handler, replacing any previous one with the same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl DirectiveHandler + 'static,
+    ) -> &mut Self {
+        self.handlers.insert(name.into(), Box::new(handler));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn DirectiveHandler> {
+        self.handlers.get(name).map(Box::as_ref)
+    }
+
+    pub fn render(
+        &self,
+        directive: &Directive,
+        renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError> {
+        match self.get(&directive.name) {
+            Some(handler) => handler.render(directive, renderer),
+            None => Ok(directive.source.clone()),
+        }
+    }
+}
+
+/// `:::note`, `:::warning`, ... boxes around regular markdown.
+pub struct Callout {
+    kind: &'static str,
+    title: &'static str,
+}
+
+impl Callout {
+    pub const fn new(kind: &'static str, title: &'static str) -> Self {
+        Self { kind, title }
+    }
+}
+
+impl DirectiveHandler for Callout {
+    fn render(
+        &self,
+        directive: &Directive,
+        renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError> {
+        let body = directive
+            .body
+            .as_deref()
+            .ok_or(DirectiveError::WrongKind("container (`:::`)"))?;
+        let title = directive.label.as_deref().unwrap_or(self.title);
+
+        Ok(format!(
+            r#"<aside class="callout callout-{kind}" role="note"><p class="callout-title">{title}</p><div class="callout-body">{body}</div></aside>"#,
+            kind = self.kind,
+            title = escape_html(title),
+            body = renderer.render(body),
+        ))
+    }
+}
+
+/// `::figure[alt]{src="/image.png" caption="..."}`
+pub struct Figure;
+
+impl DirectiveHandler for Figure {
+    fn render(
+        &self,
+        directive: &Directive,
+        _renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError> {
+        let src = directive.required("src")?;
+        let caption = directive.attribute("caption").unwrap_or_default();
+        let alt = directive
+            .attribute("alt")
+            .or(directive.label.as_deref())
+            .unwrap_or(caption);
+
+        let image = if images::is_resizable(src) {
+            images::picture_html(src, alt, images::CONTENT_SIZES, "")
+        } else {
+            format!(
+                r#"<img src="{}" alt="{}" loading="lazy"/>"#,
+                escape_html(src),
+                escape_html(alt),
+            )
+        };
+
+        let mut html = format!(r#"<figure class="figure">{image}"#);
+        if !caption.is_empty() {
+            html.push_str(&format!(
+                "<figcaption>{}</figcaption>",
+                escape_html(caption)
+            ));
+        }
+        html.push_str("</figure>");
+        Ok(html)
+    }
+}
+
+/// `::embed{src="https://codesandbox.io/embed/..." title="..."}`, limited to
+/// known sandbox hosts.
+pub struct Embed;
+
+impl Embed {
+    pub const ALLOWED_HOSTS: [&'static str; 3] = [
+        "https://codesandbox.io/",
+        "https://stackblitz.com/",
+        "https://codepen.io/",
+    ];
+}
+
+impl DirectiveHandler for Embed {
+    fn render(
+        &self,
+        directive: &Directive,
+        _renderer: &MarkdownRenderer,
+    ) -> Result<String, DirectiveError> {
+        let src = directive.required("src")?;
+        if !Self::ALLOWED_HOSTS.iter().any(|host| src.starts_with(host)) {
+            return Err(DirectiveError::InvalidAttribute {
+                attribute: "src",
+                reason: format!("host must be one of {}", Self::ALLOWED_HOSTS.join(", ")),
+            });
+        }
+        let title = directive
+            .attribute("title")
+            .or(directive.label.as_deref())
+            .unwrap_or("Embedded example");
+        let height = match directive.attribute("height") {
+            Some(height) => {
+                height
+                    .parse::<u32>()
+                    .map_err(|e| DirectiveError::InvalidAttribute {
+                        attribute: "height",
+                        reason: e.to_string(),
+                    })?
+            }
+            None => 500,
+        };
+
+        Ok(format!(
+            r#"<div class="embed"><iframe src="{}" title="{}" height="{height}" loading="lazy" sandbox="allow-scripts allow-same-origin allow-forms allow-popups"></iframe></div>"#,
+            escape_html(src),
+            escape_html(title),
+        ))
+    }
+}
+
+/// Parse the opening line of a container directive, e.g.
+/// `:::note`, `:::warning[Careful]` or `:::tip Pro tip`.
+pub fn parse_opening(line: &str) -> Option<Opening> {
+    let rest = line.trim_end().strip_prefix(":::")?;
+    if rest.starts_with(':') {
+        return None;
+    }
+    let (name, label, attributes, trailing) = parse_head(rest)?;
+    let label = label.or_else(|| (!trailing.is_empty()).then(|| trailing.to_string()));
+
+    Some(Opening {
+        name,
+        label,
+        attributes,
+    })
+}
+
+/// Parse a leaf directive occupying a whole line, e.g.
+/// `::figure{src="/a.png" caption="A caption"}`.
+pub fn parse_leaf(line: &str, source: &str) -> Option<Directive> {
+    let rest = line.trim_end().strip_prefix("::")?;
+    if rest.starts_with(':') {
+        return None;
+    }
+    let (name, label, attributes, trailing) = parse_head(rest)?;
+    if !trailing.is_empty() {
+        return None;
+    }
+
+    Some(Directive {
+        name,
+        label,
+        attributes,
+        body: None,
+        source: format!("{source}\n"),
+    })
+}
+
+type Head<'a> = (String, Option<String>, BTreeMap<String, String>, &'a str);
+
+fn parse_head(input: &str) -> Option<Head<'_>> {
+    let name_len = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(input.len());
+    let name = &input[..name_len];
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut rest = &input[name_len..];
+
+    let mut label = None;
+    if let Some(inner) = rest.strip_prefix('[') {
+        let end = inner.find(']')?;
+        label = Some(inner[..end].to_string());
+        rest = &inner[end + 1..];
+    }
+
+    let mut attributes = BTreeMap::new();
+    if let Some(inner) = rest.strip_prefix('{') {
+        let end = closing_brace(inner)?;
+        attributes = parse_attributes(&inner[..end])?;
+        rest = &inner[end + 1..];
+    }
+
+    Some((name.to_string(), label, attributes, rest.trim()))
+}
+
+fn closing_brace(input: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '}') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse `key=value key="quoted value" .class #id flag` attribute lists.
+fn parse_attributes(input: &str) -> Option<BTreeMap<String, String>> {
+    let mut attributes = BTreeMap::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return None;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(quote) => value = chars.by_ref().take_while(|c| *c != quote).collect(),
+                None => {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                }
+            }
+        }
+
+        if let Some(class) = key.strip_prefix('.') {
+            let classes = attributes
+                .entry("class".to_string())
+                .or_insert_with(String::new);
+            if !classes.is_empty() {
+                classes.push(' ');
+            }
+            classes.push_str(class);
+        } else if let Some(id) = key.strip_prefix('#') {
+            attributes.insert("id".to_string(), id.to_string());
+        } else {
+            attributes.insert(key, value);
+        }
+    }
+
+    Some(attributes)
+}
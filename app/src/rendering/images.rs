@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use super::escape_html;
+
+/// Width ladder used for `srcset` on content images.
+pub const WIDTHS: [u32; 5] = [320, 640, 960, 1280, 1920];
+
+/// Widths below the ladder for small fixed-size images, like the 40px logo
+/// and its 2x variant.
+pub const SMALL_WIDTHS: [u32; 3] = [40, 80, 160];
+
+/// Largest width the image endpoint generates.
+pub const MAX_WIDTH: u32 = 1920;
+
+/// `sizes` hint matching the `max-w-4xl` content column of blog posts.
+pub const CONTENT_SIZES: &str = "(min-width: 896px) 832px, 100vw";
+
+/// Route prefix served by the server's image handler.
+pub const IMAGE_ROUTE: &str = "/img";
+
+static IMAGE_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set the directory local images are read from, used to look up their
+/// intrinsic dimensions. Called once by the server at startup.
+pub fn set_image_root(root: impl Into<PathBuf>) {
+    let _ = IMAGE_ROOT.set(root.into());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Avif => "image/avif",
+        }
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webp" => Ok(ImageFormat::Webp),
+            "avif" => Ok(ImageFormat::Avif),
+            _ => Err(format!("unsupported image format: {s}")),
+        }
+    }
+}
+
+/// Whether `src` points to a local raster image the pipeline can resize.
+pub fn is_resizable(src: &str) -> bool {
+    let path = src.split(['?', '#']).next().unwrap_or_default();
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && [".png", ".jpg", ".jpeg"]
+            .iter()
+            .any(|ext| path.to_ascii_lowercase().ends_with(ext))
+}
+
+/// Intrinsic `(width, height)` of a local image, when it can be read.
+pub fn dimensions(src: &str) -> Option<(u32, u32)> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "ssr")] {
+            let root = IMAGE_ROOT.get()?;
+            let relative = src.trim_start_matches('/');
+            if relative.split('/').any(|segment| segment == "..") {
+                return None;
+            }
+            let size = imagesize::size(root.join(relative)).ok()?;
+            Some((size.width as u32, size.height as u32))
+        } else {
+            let _ = src;
+            None
+        }
+    }
+}
+
+/// URL of a resized variant of `src`.
+pub fn variant_url(src: &str, width: u32, format: ImageFormat) -> String {
+    format!("{IMAGE_ROUTE}{src}?w={width}&fmt={}", format.extension())
+}
+
+/// `srcset` value for `src`, limited to widths the source can fill.
+pub fn srcset(src: &str, format: ImageFormat, max_width: Option<u32>) -> String {
+    let mut widths: Vec<u32> = WIDTHS
+        .into_iter()
+        .filter(|width| max_width.is_none_or(|max| *width <= max))
+        .collect();
+    if let Some(max) = max_width.filter(|max| !WIDTHS.contains(max)) {
+        widths.push(max);
+    }
+
+    widths
+        .into_iter()
+        .map(|width| format!("{} {width}w", variant_url(src, width, format)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render a `<picture>` with AVIF and WebP sources for a local image, the
+/// original kept as the `<img>` fallback.
+pub fn picture_html(src: &str, alt: &str, sizes: &str, extra_attributes: &str) -> String {
+    let dimensions = dimensions(src);
+    let max_width = dimensions.map(|(width, _)| width);
+    let size_attributes = dimensions
+        .map(|(width, height)| format!(r#" width="{width}" height="{height}""#))
+        .unwrap_or_default();
+
+    format!(
+        r#"<picture><source type="{avif_type}" srcset="{avif}" sizes="{sizes}"/><source type="{webp_type}" srcset="{webp}" sizes="{sizes}"/><img src="{src}" alt="{alt}"{size_attributes} loading="lazy" decoding="async"{extra_attributes}/></picture>"#,
+        avif_type = ImageFormat::Avif.mime_type(),
+        avif = escape_html(&srcset(src, ImageFormat::Avif, max_width)),
+        webp_type = ImageFormat::Webp.mime_type(),
+        webp = escape_html(&srcset(src, ImageFormat::Webp, max_width)),
+        sizes = escape_html(sizes),
+        src = escape_html(src),
+        alt = escape_html(alt),
+    )
+}
+
+/// Replace the `<img>` tags produced by the markdown renderer for local
+/// images with responsive `<picture>` elements.
+pub fn rewrite_images(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<img ") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..=end];
+        rest = &rest[end + 1..];
+
+        match attribute(tag, "src").filter(|src| is_resizable(src)) {
+            Some(src) => {
+                let alt = attribute(tag, "alt").unwrap_or_default();
+                let title = attribute(tag, "title")
+                    .map(|title| format!(r#" title="{}""#, escape_html(&title)))
+                    .unwrap_or_default();
+                output.push_str(&picture_html(&src, &alt, CONTENT_SIZES, &title));
+            }
+            None => output.push_str(tag),
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Read a double-quoted attribute from an HTML tag written by the
+/// markdown renderer, undoing its entity escaping.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let needle = format!(r#" {name}=""#);
+    let start = tag.find(&needle)? + needle.len();
+    let end = tag[start..].find('"')?;
+    Some(
+        tag[start..start + end]
+            .replace("&quot;", "\"")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srcset() {
+        assert_eq!(
+            srcset("/cover.png", ImageFormat::Avif, Some(700)),
+            "/img/cover.png?w=320&fmt=avif 320w, /img/cover.png?w=640&fmt=avif 640w, \
+             /img/cover.png?w=700&fmt=avif 700w"
+        );
+        assert_eq!(
+            srcset("/cover.png", ImageFormat::Webp, Some(640)),
+            "/img/cover.png?w=320&fmt=webp 320w, /img/cover.png?w=640&fmt=webp 640w"
+        );
+        assert_eq!(
+            srcset("/cover.png", ImageFormat::Webp, None)
+                .matches(", ")
+                .count(),
+            4
+        );
+    }
+}
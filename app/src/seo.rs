@@ -0,0 +1,123 @@
+use leptos::prelude::*;
+use leptos_meta::{Link, Meta, Title};
+use serde_json::{json, Value};
+
+use crate::posts::BlogPostData;
+use crate::site::{absolute_url, og_image_path, SITE_URL};
+
+pub const SITE_NAME: &str = "Frans Ramirez Neyra";
+pub const AUTHOR_NAME: &str = "Frans Ramirez Neyra";
+
+/// Open Graph object type of a page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageKind {
+    #[default]
+    Website,
+    Article,
+    Profile,
+}
+
+impl PageKind {
+    fn og_type(&self) -> &'static str {
+        match self {
+            PageKind::Website => "website",
+            PageKind::Article => "article",
+            PageKind::Profile => "profile",
+        }
+    }
+}
+
+/// Per-route document metadata: title, description, canonical URL, Open
+/// Graph and Twitter tags, plus optional JSON-LD structured data.
+#[component]
+pub fn Seo(
+    #[prop(into)] title: String,
+    #[prop(into)] description: String,
+    /// Site-relative path of the canonical URL, e.g. `/blog`.
+    #[prop(into)]
+    path: String,
+    #[prop(optional)] kind: PageKind,
+    /// Site-relative path of the preview image, defaults to the logo.
+    #[prop(optional, into)]
+    image: Option<String>,
+    /// Width and height of `image`, when they are known.
+    #[prop(optional)]
+    image_size: Option<(u32, u32)>,
+    /// ISO 8601 publication date for articles.
+    #[prop(optional, into)]
+    published: Option<String>,
+    #[prop(optional)] json_ld: Option<Value>,
+) -> impl IntoView {
+    let canonical = absolute_url(&path);
+    let image = absolute_url(image.as_deref().unwrap_or("/logo.png"));
+    let twitter_card = if kind == PageKind::Article {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+
+    view! {
+        <Title text=title.clone()/>
+        <Meta name="description" content=description.clone()/>
+        <Link rel="canonical" href=canonical.clone()/>
+
+        <Meta property="og:site_name" content=SITE_NAME/>
+        <Meta property="og:type" content=kind.og_type()/>
+        <Meta property="og:title" content=title.clone()/>
+        <Meta property="og:description" content=description.clone()/>
+        <Meta property="og:url" content=canonical/>
+        <Meta property="og:image" content=image.clone()/>
+        {image_size
+            .map(|(width, height)| {
+                view! {
+                    <Meta property="og:image:width" content=width.to_string()/>
+                    <Meta property="og:image:height" content=height.to_string()/>
+                }
+            })}
+        {published.map(|date| view! { <Meta property="article:published_time" content=date/> })}
+
+        <Meta name="twitter:card" content=twitter_card/>
+        <Meta name="twitter:title" content=title/>
+        <Meta name="twitter:description" content=description/>
+        <Meta name="twitter:image" content=image/>
+
+        {json_ld.map(|data| view! { <JsonLd data/> })}
+    }
+}
+
+/// Structured data script. `<` is escaped so the payload can never close
+/// the surrounding `<script>` element.
+#[component]
+pub fn JsonLd(data: Value) -> impl IntoView {
+    let payload = data.to_string().replace('<', "\\u003c");
+
+    view! { <script type="application/ld+json" inner_html=payload></script> }
+}
+
+/// Schema.org `Person` describing the site author.
+pub fn author() -> Value {
+    json!({
+        "@type": "Person",
+        "name": AUTHOR_NAME,
+        "url": SITE_URL,
+    })
+}
+
+/// Schema.org `BlogPosting` for a post.
+pub fn blog_posting(post: &BlogPostData) -> Value {
+    let url = absolute_url(&format!("/blog/{}", post.slug));
+
+    json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": post.title,
+        "description": post.excerpt,
+        "articleSection": post.category,
+        "datePublished": post.date,
+        "image": absolute_url(&og_image_path(&post.slug)),
+        "url": url,
+        "mainEntityOfPage": { "@type": "WebPage", "@id": url },
+        "author": author(),
+        "publisher": author(),
+    })
+}
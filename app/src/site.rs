@@ -0,0 +1,16 @@
+/// Public origin of the site, used wherever an absolute URL is required
+/// (Open Graph tags, canonical links, structured data).
+pub const SITE_URL: &str = "https://fransramirez.com";
+
+/// Turn a site-relative path into an absolute URL.
+pub fn absolute_url(path: &str) -> String {
+    format!("{SITE_URL}/{}", path.trim_start_matches('/'))
+}
+
+/// Width and height of the generated Open Graph cards.
+pub const OG_IMAGE_SIZE: (u32, u32) = (1200, 630);
+
+/// Path of the generated Open Graph card for a blog post.
+pub fn og_image_path(slug: &str) -> String {
+    format!("/og/{slug}.png")
+}
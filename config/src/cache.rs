@@ -0,0 +1,168 @@
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// How responses on a route may be cached by browsers and proxies.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Never stored, for API and personalised responses.
+    NoStore,
+    /// Stored but checked with the server on every use through the `ETag`.
+    Revalidate,
+    /// Reused for the given number of seconds, then revalidated.
+    Public(u32),
+    /// Never changes, for assets with a hash in their file name.
+    Immutable,
+}
+
+impl CachePolicy {
+    /// Value of the `Cache-Control` header.
+    pub fn cache_control(&self) -> String {
+        match self {
+            CachePolicy::NoStore => "no-store".to_string(),
+            CachePolicy::Revalidate => "no-cache".to_string(),
+            CachePolicy::Public(seconds) => format!("public, max-age={seconds}"),
+            CachePolicy::Immutable => "public, max-age=31536000, immutable".to_string(),
+        }
+    }
+
+    /// Whether responses get an `ETag` and conditional requests are answered
+    /// with `304 Not Modified`.
+    pub fn uses_etag(&self) -> bool {
+        matches!(self, CachePolicy::Revalidate | CachePolicy::Public(_))
+    }
+}
+
+impl FromStr for CachePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("public", seconds)) => seconds
+                .parse()
+                .map(CachePolicy::Public)
+                .map_err(|e| format!("invalid max-age in `{s}`: {e}")),
+            None if s == "no-store" => Ok(CachePolicy::NoStore),
+            None if s == "revalidate" => Ok(CachePolicy::Revalidate),
+            None if s == "immutable" => Ok(CachePolicy::Immutable),
+            _ => Err(format!(
+                "unknown policy `{s}`, expected no-store, revalidate, public:<seconds> or immutable"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CachePolicy::NoStore => f.write_str("no-store"),
+            CachePolicy::Revalidate => f.write_str("revalidate"),
+            CachePolicy::Public(seconds) => write!(f, "public:{seconds}"),
+            CachePolicy::Immutable => f.write_str("immutable"),
+        }
+    }
+}
+
+/// A route pattern and its policy. Patterns are exact paths, or prefixes
+/// when they end in `/*`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct CacheRule {
+    pub pattern: String,
+    pub policy: CachePolicy,
+}
+
+impl CacheRule {
+    pub fn matches(&self, path: &str) -> bool {
+        pattern_matches(&self.pattern, path)
+    }
+}
+
+pub(crate) fn pattern_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => path == pattern,
+    }
+}
+
+/// Cache policies by route, the first matching rule wins. Routes without a
+/// rule keep whatever their handler sets.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CacheConfig {
+    pub routes: Vec<CacheRule>,
+}
+
+impl CacheConfig {
+    pub fn policy_for(&self, path: &str) -> Option<CachePolicy> {
+        self.routes
+            .iter()
+            .find(|rule| rule.matches(path))
+            .map(|rule| rule.policy)
+    }
+}
+
+/// The server's in-memory cache of rendered pages, served to anonymous
+/// visitors without running SSR again.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageCacheConfig {
+    /// How long a page is served from memory before it is rendered again,
+    /// `0` turns the cache off.
+    pub ttl_secs: u64,
+    /// How much longer an expired page is still served while a fresh copy
+    /// renders in the background.
+    pub stale_secs: u64,
+    /// Most pages kept at once, the oldest is evicted first.
+    pub max_entries: usize,
+    /// Patterns of the paths that are cached, in the same form as
+    /// [`CacheRule`] patterns.
+    pub routes: Vec<String>,
+}
+
+impl PageCacheConfig {
+    pub fn enabled(&self) -> bool {
+        self.ttl_secs > 0 && self.max_entries > 0
+    }
+
+    pub fn caches(&self, path: &str) -> bool {
+        self.routes
+            .iter()
+            .any(|pattern| pattern_matches(pattern, path))
+    }
+}
+
+/// Parse the comma separated path patterns of `PAGE_CACHE_ROUTES`.
+pub(crate) fn parse_patterns(value: &str) -> Result<Vec<String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| {
+            if pattern.starts_with('/') {
+                Ok(pattern.to_string())
+            } else {
+                Err(format!("pattern `{pattern}` must start with /"))
+            }
+        })
+        .collect()
+}
+
+/// Parse the comma separated `<pattern>=<policy>` list of `CACHE_ROUTES`.
+pub(crate) fn parse_routes(value: &str) -> Result<Vec<CacheRule>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (pattern, policy) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("`{rule}` is not <pattern>=<policy>"))?;
+            let pattern = pattern.trim();
+            if !pattern.starts_with('/') {
+                return Err(format!("pattern `{pattern}` must start with /"));
+            }
+            Ok(CacheRule {
+                pattern: pattern.to_string(),
+                policy: policy.trim().parse()?,
+            })
+        })
+        .collect()
+}
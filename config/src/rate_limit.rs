@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::cache::pattern_matches;
+
+/// Where request counts are kept.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub enum RateLimitStore {
+    /// In the process, each machine limits on its own.
+    #[default]
+    Memory,
+    /// In the `rate_limits` table, shared by every machine.
+    Postgres,
+}
+
+impl FromStr for RateLimitStore {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "memory" => Ok(RateLimitStore::Memory),
+            "postgres" => Ok(RateLimitStore::Postgres),
+            _ => Err("must be one of: memory, postgres".to_string()),
+        }
+    }
+}
+
+/// A route pattern and the requests per minute a client may make to it.
+/// Patterns are exact paths, or prefixes when they end in `/*`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct RateLimitRule {
+    pub pattern: String,
+    pub per_minute: u32,
+}
+
+impl RateLimitRule {
+    pub fn matches(&self, path: &str) -> bool {
+        pattern_matches(&self.pattern, path)
+    }
+}
+
+/// An address range in CIDR notation, a bare address is a range of one.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub struct IpRange {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("`{s}` is not an IP address or CIDR range"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            "" => max,
+            prefix => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length in `{s}`"))?,
+        };
+        Ok(IpRange { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Request budgets, enforced per client IP, or per user for authenticated
+/// requests.
+///
+/// Routes with a rule get its budget, other requests that change state
+/// (forms and server functions) share `runtime.rate_limit_per_minute`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RateLimitConfig {
+    pub routes: Vec<RateLimitRule>,
+    /// Proxies whose `Fly-Client-IP` and `X-Forwarded-For` headers are
+    /// believed. Requests from anywhere else are keyed by the peer address.
+    pub trusted_proxies: Vec<IpRange>,
+    pub store: RateLimitStore,
+}
+
+impl RateLimitConfig {
+    /// The first rule matching `path`.
+    pub fn rule_for(&self, path: &str) -> Option<&RateLimitRule> {
+        self.routes.iter().find(|rule| rule.matches(path))
+    }
+
+    pub fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|range| range.contains(ip))
+    }
+}
+
+/// Parse the comma separated `<pattern>=<per minute>` list of
+/// `RATE_LIMIT_ROUTES`.
+pub(crate) fn parse_routes(value: &str) -> Result<Vec<RateLimitRule>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (pattern, per_minute) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("`{rule}` is not <pattern>=<per minute>"))?;
+            let pattern = pattern.trim();
+            if !pattern.starts_with('/') {
+                return Err(format!("pattern `{pattern}` must start with /"));
+            }
+            let per_minute = per_minute
+                .trim()
+                .parse()
+                .ok()
+                .filter(|per_minute| *per_minute > 0)
+                .ok_or_else(|| format!("budget in `{rule}` must be a number above 0"))?;
+            Ok(RateLimitRule {
+                pattern: pattern.to_string(),
+                per_minute,
+            })
+        })
+        .collect()
+}
+
+/// Parse the comma separated ranges of `RATE_LIMIT_TRUSTED_PROXIES`.
+pub(crate) fn parse_ranges(value: &str) -> Result<Vec<IpRange>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .map(str::parse)
+        .collect()
+}
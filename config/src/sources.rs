@@ -0,0 +1,572 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{bool_value, redact_url, ConfigError, Environment};
+
+/// Where a configuration value came from, from lowest to highest priority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Built-in default.
+    Default,
+    /// A TOML file: the base file, a profile or the local override.
+    File(PathBuf),
+    /// The `.env` file in the working directory.
+    DotEnv,
+    /// A process environment variable.
+    Environment,
+    /// A file named by a `<KEY>_FILE` variable, e.g. a mounted Docker or
+    /// Fly secret.
+    SecretFile(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::DotEnv => write!(f, ".env"),
+            Source::Environment => write!(f, "environment"),
+            Source::SecretFile(path) => write!(f, "secret file {}", path.display()),
+        }
+    }
+}
+
+/// A configuration setting: its environment variable, its `section.key`
+/// path in the TOML files, the type it has there and its built-in default.
+///
+/// Secret fields are redacted when layers are printed and can also be read
+/// from the file named by `<KEY>_FILE`. Reloadable fields are picked up by a
+/// running server on reload, every other change needs a restart.
+pub struct Field {
+    pub env: &'static str,
+    pub path: &'static str,
+    pub kind: ValueKind,
+    pub default: Option<&'static str>,
+    pub secret: bool,
+    pub reloadable: bool,
+}
+
+/// How a value is written in the TOML files. Variables hold the same values
+/// as strings, lists comma separated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Integer,
+    Boolean,
+    List,
+}
+
+impl Field {
+    /// `value` as it would be written in the TOML files. A value that isn't
+    /// valid for the field stays a string, to show what was set.
+    pub fn toml_value(&self, value: &str) -> toml::Value {
+        let string = || toml::Value::String(value.to_string());
+        match self.kind {
+            ValueKind::String => string(),
+            ValueKind::Integer => value
+                .parse()
+                .map_or_else(|_| string(), toml::Value::Integer),
+            ValueKind::Boolean => bool_value(value).map_or_else(string, toml::Value::Boolean),
+            ValueKind::List => toml::Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub const FIELDS: &[Field] = &[
+    Field {
+        env: "HOST",
+        path: "server.host",
+        kind: ValueKind::String,
+        default: Some("127.0.0.1"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "PORT",
+        path: "server.port",
+        kind: ValueKind::Integer,
+        default: Some("3000"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "LEPTOS_OUTPUT_NAME",
+        path: "server.leptos_output_name",
+        kind: ValueKind::String,
+        default: Some("webpage"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "LEPTOS_SITE_ROOT",
+        path: "server.leptos_site_root",
+        kind: ValueKind::String,
+        default: Some("target/site"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "LEPTOS_SITE_PKG_DIR",
+        path: "server.leptos_site_pkg_dir",
+        kind: ValueKind::String,
+        default: Some("pkg"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "SHUTDOWN_TIMEOUT_SECS",
+        path: "server.shutdown_timeout_secs",
+        kind: ValueKind::Integer,
+        default: Some("25"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "DATABASE_URL",
+        path: "database.url",
+        kind: ValueKind::String,
+        default: None,
+        secret: true,
+        reloadable: false,
+    },
+    Field {
+        env: "DATABASE_MAX_CONNECTIONS",
+        path: "database.max_connections",
+        kind: ValueKind::Integer,
+        default: Some("10"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "ENVIRONMENT",
+        path: "app.environment",
+        kind: ValueKind::String,
+        default: Some("development"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "LOG_LEVEL",
+        path: "app.log_level",
+        kind: ValueKind::String,
+        default: Some("info"),
+        secret: false,
+        reloadable: true,
+    },
+    Field {
+        env: "LOG_FORMAT",
+        path: "app.log_format",
+        kind: ValueKind::String,
+        default: Some("pretty"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "JWT_SECRET",
+        path: "app.jwt_secret",
+        kind: ValueKind::String,
+        default: None,
+        secret: true,
+        reloadable: false,
+    },
+    Field {
+        env: "JWT_PREVIOUS_SECRETS",
+        path: "app.jwt_previous_secrets",
+        kind: ValueKind::List,
+        default: None,
+        secret: true,
+        reloadable: false,
+    },
+    Field {
+        env: "CSP_REPORT_ONLY",
+        path: "security.csp_report_only",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "CACHE_ROUTES",
+        path: "cache.routes",
+        kind: ValueKind::List,
+        default: Some(
+            "/pkg/*=immutable,/=revalidate,/blog=revalidate,/blog/*=revalidate,/api/*=no-store",
+        ),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "PAGE_CACHE_TTL_SECS",
+        path: "page_cache.ttl_secs",
+        kind: ValueKind::Integer,
+        default: Some("60"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "PAGE_CACHE_STALE_SECS",
+        path: "page_cache.stale_secs",
+        kind: ValueKind::Integer,
+        default: Some("3600"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "PAGE_CACHE_MAX_ENTRIES",
+        path: "page_cache.max_entries",
+        kind: ValueKind::Integer,
+        default: Some("500"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "PAGE_CACHE_ROUTES",
+        path: "page_cache.routes",
+        kind: ValueKind::List,
+        default: Some("/,/blog,/blog/*"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "METRICS_ADDR",
+        path: "metrics.addr",
+        kind: ValueKind::String,
+        default: None,
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "METRICS_TOKEN",
+        path: "metrics.token",
+        kind: ValueKind::String,
+        default: None,
+        secret: true,
+        reloadable: false,
+    },
+    Field {
+        env: "FEATURES",
+        path: "runtime.features",
+        kind: ValueKind::List,
+        default: Some(""),
+        secret: false,
+        reloadable: true,
+    },
+    Field {
+        env: "RATE_LIMIT_PER_MINUTE",
+        path: "runtime.rate_limit_per_minute",
+        kind: ValueKind::Integer,
+        default: Some("120"),
+        secret: false,
+        reloadable: true,
+    },
+    Field {
+        env: "RATE_LIMIT_ROUTES",
+        path: "rate_limit.routes",
+        kind: ValueKind::List,
+        default: Some("/api/*=60,/s/*=30"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "RATE_LIMIT_TRUSTED_PROXIES",
+        path: "rate_limit.trusted_proxies",
+        kind: ValueKind::List,
+        default: Some(""),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "RATE_LIMIT_STORE",
+        path: "rate_limit.store",
+        kind: ValueKind::String,
+        default: Some("memory"),
+        secret: false,
+        reloadable: false,
+    },
+    Field {
+        env: "MAINTENANCE_MODE",
+        path: "runtime.maintenance",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        secret: false,
+        reloadable: true,
+    },
+    Field {
+        env: "MAINTENANCE_RETRY_AFTER_SECS",
+        path: "runtime.maintenance_retry_after_secs",
+        kind: ValueKind::Integer,
+        default: Some("300"),
+        secret: false,
+        reloadable: true,
+    },
+];
+
+/// Directory holding the TOML files, overridable with `CONFIG_DIR`.
+pub const DEFAULT_CONFIG_DIR: &str = "config";
+
+/// Directory the TOML files are read from.
+pub fn config_dir() -> PathBuf {
+    std::env::var("CONFIG_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_DIR))
+}
+
+/// Configuration values merged from every layer, each remembering the
+/// source it was taken from.
+///
+/// Layers, from lowest to highest priority: built-in defaults,
+/// `default.toml`, the `<environment>.toml` profile, `local.toml`, `.env`
+/// and finally process environment variables. Within the `.env` and
+/// environment layers a secret can be given either directly or through
+/// `<KEY>_FILE`, but not both.
+///
+/// Files and secrets that can't be read are kept as [`Layers::problems`]
+/// and the other layers still load, so they are reported together with the
+/// invalid values.
+#[derive(Clone, Default)]
+pub struct Layers {
+    values: BTreeMap<&'static str, (String, Source)>,
+    problems: Vec<ConfigError>,
+}
+
+impl Layers {
+    /// Load every layer. `env` looks up process environment variables and
+    /// `dotenv` holds the pairs read from the `.env` file.
+    pub fn load(
+        config_dir: &Path,
+        dotenv: &[(String, String)],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let mut layers = Self::defaults();
+        layers.merge_file(&config_dir.join("default.toml"));
+        // Read ahead of the profile since it can pick the environment, but
+        // merged after it
+        let mut local = Self::default();
+        local.merge_file(&config_dir.join("local.toml"));
+
+        // The profile depends on the environment, which itself can come from
+        // any layer above the profile files. An invalid one is reported by
+        // `Config::from_layers`, only the profile is skipped here.
+        let environment = env("ENVIRONMENT")
+            .or_else(|| lookup(dotenv, "ENVIRONMENT"))
+            .or_else(|| local.get("ENVIRONMENT").map(str::to_string))
+            .or_else(|| layers.get("ENVIRONMENT").map(str::to_string))
+            .unwrap_or_default();
+        if let Ok(environment) = environment.parse::<Environment>() {
+            layers.merge_file(&config_dir.join(format!("{}.toml", environment.profile())));
+        }
+        layers.values.extend(local.values);
+        layers.problems.extend(local.problems);
+
+        for field in FIELDS {
+            layers.merge_variable(field, lookup(dotenv, field.env), Source::DotEnv, |key| {
+                lookup(dotenv, key)
+            });
+            layers.merge_variable(field, env(field.env), Source::Environment, &env);
+        }
+
+        layers
+    }
+
+    /// Load every layer for this process: the TOML files in `CONFIG_DIR`
+    /// (or [`DEFAULT_CONFIG_DIR`]), `.env` and the environment.
+    pub fn discover() -> Self {
+        let config_dir = config_dir();
+        // `.env` is useful for development, it is read as its own layer
+        // instead of being exported into the process environment, so only
+        // the configuration sees its values, not other libraries or child
+        // processes
+        let dotenv: Vec<(String, String)> = dotenvy::dotenv_iter()
+            .map(|iter| iter.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+
+        Self::load(&config_dir, &dotenv, |key| std::env::var(key).ok())
+    }
+
+    /// Files and secret files that could not be read while loading.
+    pub fn problems(&self) -> &[ConfigError] {
+        &self.problems
+    }
+
+    /// Only the built-in defaults.
+    pub fn defaults() -> Self {
+        let mut layers = Self::default();
+        for field in FIELDS {
+            if let Some(default) = field.default {
+                layers.set(field.env, default.to_string(), Source::Default);
+            }
+        }
+        layers
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|(value, _)| value.as_str())
+    }
+
+    pub fn source(&self, key: &str) -> Option<&Source> {
+        self.values.get(key).map(|(_, source)| source)
+    }
+
+    /// Set a value, replacing the one from any lower layer. Keys that are
+    /// not in [`FIELDS`] are ignored.
+    pub fn set(&mut self, key: &str, value: String, source: Source) {
+        if let Some(field) = FIELDS.iter().find(|field| field.env == key) {
+            self.values.insert(field.env, (value, source));
+        }
+    }
+
+    /// A value that is safe to print: secrets are replaced, except for the
+    /// database URL which only has its password masked.
+    pub fn redacted(&self, key: &str) -> Option<String> {
+        let value = self.get(key)?;
+        let secret = FIELDS.iter().any(|field| field.env == key && field.secret);
+        Some(match key {
+            "DATABASE_URL" => redact_url(value),
+            _ if secret => "[REDACTED]".to_string(),
+            _ => value.to_string(),
+        })
+    }
+
+    /// `(key, source)` for every value that is set, in key order.
+    pub fn report(&self) -> impl Iterator<Item = (&'static str, &Source)> {
+        self.values.iter().map(|(key, (_, source))| (*key, source))
+    }
+
+    /// Merge a field from a variable layer, following `<KEY>_FILE` for
+    /// secrets.
+    fn merge_variable(
+        &mut self,
+        field: &Field,
+        value: Option<String>,
+        source: Source,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) {
+        let file_key = format!("{}_FILE", field.env);
+        let file = if field.secret {
+            lookup(&file_key)
+        } else {
+            None
+        };
+
+        match (value, file) {
+            (Some(_), Some(_)) => self.problems.push(ConfigError::InvalidEnvVar {
+                key: file_key,
+                value: "[REDACTED]".to_string(),
+                reason: format!("{} is also set, use only one of them", field.env),
+            }),
+            (Some(value), None) => self.set(field.env, value, source),
+            (None, Some(path)) => {
+                let path = PathBuf::from(path);
+                match std::fs::read_to_string(&path) {
+                    // Secret files usually end with a newline that isn't
+                    // part of the value
+                    Ok(contents) => {
+                        let value = contents.trim_end_matches(['\n', '\r']).to_string();
+                        self.set(field.env, value, Source::SecretFile(path));
+                    }
+                    Err(err) => self.problems.push(file_error(&path, err.to_string())),
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Merge a TOML file if it exists. Unknown keys are rejected so typos
+    /// don't silently fall back to defaults, the rest of the file still
+    /// applies.
+    fn merge_file(&mut self, path: &Path) {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => return self.problems.push(file_error(path, err.to_string())),
+        };
+        let table: toml::Table = match contents.parse() {
+            Ok(table) => table,
+            Err(err) => {
+                let err: toml::de::Error = err;
+                return self
+                    .problems
+                    .push(file_error(path, err.message().to_string()));
+            }
+        };
+
+        for (section, values) in table {
+            let toml::Value::Table(values) = values else {
+                self.problems
+                    .push(file_error(path, format!("`{section}` must be a table")));
+                continue;
+            };
+            for (key, value) in values {
+                let dotted = format!("{section}.{key}");
+                match file_value(&dotted, value) {
+                    Ok(Some(field)) => self.set(field.0, field.1, Source::File(path.to_path_buf())),
+                    Ok(None) => self
+                        .problems
+                        .push(file_error(path, format!("unknown key `{dotted}`"))),
+                    Err(reason) => self.problems.push(file_error(path, reason)),
+                }
+            }
+        }
+    }
+}
+
+/// The variable a `section.key` of a TOML file sets and its value, `None`
+/// for unknown keys.
+fn file_value(dotted: &str, value: toml::Value) -> Result<Option<(&'static str, String)>, String> {
+    let Some(field) = FIELDS.iter().find(|field| field.path == dotted) else {
+        return Ok(None);
+    };
+    let value = match value {
+        toml::Value::String(value) => value,
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Boolean(value) => value.to_string(),
+        // Lists use the same comma separated form as variables
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("`{dotted}` must be a list of strings"))?
+            .join(","),
+        _ => {
+            return Err(format!(
+                "`{dotted}` must be a string, integer, boolean or list"
+            ))
+        }
+    };
+    Ok(Some((field.env, value)))
+}
+
+impl fmt::Debug for Layers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (key, (value, source)) in &self.values {
+            let secret = FIELDS.iter().any(|field| field.env == *key && field.secret);
+            let value = if secret { "[REDACTED]" } else { value.as_str() };
+            map.entry(key, &format_args!("{value:?} ({source})"));
+        }
+        map.finish()
+    }
+}
+
+fn lookup(pairs: &[(String, String)], key: &str) -> Option<String> {
+    pairs
+        .iter()
+        .rev()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.clone())
+}
+
+fn file_error(path: &Path, reason: String) -> ConfigError {
+    ConfigError::FileError {
+        path: path.display().to_string(),
+        reason,
+    }
+}
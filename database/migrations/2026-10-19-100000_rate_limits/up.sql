@@ -0,0 +1,7 @@
+-- Request counts per client and one minute window, shared by every machine
+CREATE TABLE "rate_limits"(
+	"key" VARCHAR NOT NULL,
+	"window_start" BIGINT NOT NULL,
+	"hits" INTEGER NOT NULL,
+	PRIMARY KEY ("key", "window_start")
+);
@@ -0,0 +1,11 @@
+-- Redirects consulted before routing. A source ending in /* matches every
+-- path below it, 410 rules have no target.
+CREATE TABLE "redirects"(
+	"id" SERIAL PRIMARY KEY,
+	"source" VARCHAR NOT NULL UNIQUE,
+	"target" VARCHAR,
+	"status" SMALLINT NOT NULL CHECK ("status" IN (301, 302, 410)),
+	"created_at" TIMESTAMPTZ NOT NULL DEFAULT now(),
+	"updated_at" TIMESTAMPTZ NOT NULL DEFAULT now(),
+	CHECK (("status" = 410) = ("target" IS NULL))
+);
@@ -0,0 +1,20 @@
+-- Short links under /s/{code}. Every post gets a generated code, vanity
+-- codes are picked by hand.
+CREATE TABLE "short_links"(
+	"id" SERIAL PRIMARY KEY,
+	"code" VARCHAR NOT NULL UNIQUE,
+	"post_slug" VARCHAR NOT NULL,
+	"vanity" BOOLEAN NOT NULL DEFAULT FALSE,
+	"created_at" TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX "short_links_post_slug_idx" ON "short_links"("post_slug");
+
+-- Clicks per link, day and referring host, '' when there was no referrer.
+CREATE TABLE "short_link_clicks"(
+	"short_link_id" INTEGER NOT NULL REFERENCES "short_links"("id") ON DELETE CASCADE,
+	"day" DATE NOT NULL,
+	"referrer" VARCHAR NOT NULL,
+	"clicks" INTEGER NOT NULL,
+	PRIMARY KEY ("short_link_id", "day", "referrer")
+);
@@ -0,0 +1,8 @@
+-- Maintenance mode set by an admin, overriding runtime.maintenance on every
+-- machine. At most one row, none while the configuration applies.
+CREATE TABLE "maintenance_override"(
+	"id" BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK ("id"),
+	"enabled" BOOLEAN NOT NULL,
+	"set_by" VARCHAR NOT NULL,
+	"updated_at" TIMESTAMPTZ NOT NULL DEFAULT now()
+);
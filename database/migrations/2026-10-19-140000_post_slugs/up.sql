@@ -0,0 +1,7 @@
+-- New slugs of renamed posts, by the slug the post was written with. The
+-- old URLs are kept working by a 301 in redirects.
+CREATE TABLE "post_slugs"(
+	"original_slug" VARCHAR PRIMARY KEY,
+	"slug" VARCHAR NOT NULL UNIQUE,
+	"updated_at" TIMESTAMPTZ NOT NULL DEFAULT now()
+);
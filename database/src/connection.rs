@@ -1,13 +1,19 @@
-use diesel::{Connection, PgConnection};
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
+use diesel::sql_types::Text;
+use diesel::{Connection, PgConnection, QueryableByName};
 use diesel_async::{
     pooled_connection::{
-        bb8::{Pool, PooledConnection},
-        AsyncDieselConnectionManager,
+        bb8::{Pool, PooledConnection, RunError},
+        AsyncDieselConnectionManager, ManagerConfig,
     },
-    AsyncPgConnection,
+    AsyncConnection, AsyncPgConnection, RunQueryDsl,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::collections::HashSet;
 use std::error::Error;
+use std::time::Instant;
 use tokio::sync::OnceCell;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -16,30 +22,111 @@ pub type DbPool = Pool<AsyncPgConnection>;
 
 pub type DbConnection<'a> = PooledConnection<'a, AsyncPgConnection>;
 
-pub async fn create_pool(db_url: &String) -> Result<DbPool, Box<dyn std::error::Error>> {
-    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
-    let pool = Pool::builder().max_size(15).build(config).await?;
+pub async fn create_pool(
+    db_url: &String,
+    max_connections: u32,
+) -> Result<DbPool, Box<dyn std::error::Error>> {
+    let mut manager_config = ManagerConfig::default();
+    manager_config.custom_setup = Box::new(|url| {
+        Box::pin(async move {
+            let mut connection = AsyncPgConnection::establish(url).await?;
+            connection.set_instrumentation(QueryLogger::default());
+            Ok(connection)
+        })
+    });
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder()
+        .max_size(max_connections)
+        .build(config)
+        .await?;
     Ok(pool)
 }
 
-pub async fn get_connection_pool(db_url: &String) -> &'static Pool<AsyncPgConnection> {
+/// Logs every query at debug level, inside the span of the request that
+/// runs it. Bind values are left out since they can hold user data.
+#[derive(Default)]
+struct QueryLogger {
+    started: Option<Instant>,
+}
+
+impl Instrumentation for QueryLogger {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                let elapsed_ms = self
+                    .started
+                    .take()
+                    .map(|started| started.elapsed().as_secs_f64() * 1000.0);
+                let query = query.to_string();
+                let sql = query.split(" -- binds:").next().unwrap_or_default();
+                match error {
+                    Some(error) => tracing::warn!(sql, elapsed_ms, %error, "query failed"),
+                    None => tracing::debug!(sql, elapsed_ms, "query"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check a connection out of the pool, recording how long it took in the
+/// `db_pool_wait_seconds` histogram.
+pub async fn acquire(pool: &DbPool) -> Result<DbConnection<'_>, RunError> {
+    let started = Instant::now();
+    let connection = pool.get().await;
+    metrics::histogram!("db_pool_wait_seconds").record(started.elapsed().as_secs_f64());
+    connection
+}
+
+pub async fn get_connection_pool(
+    db_url: &String,
+    max_connections: u32,
+) -> &'static Pool<AsyncPgConnection> {
     static POOL: OnceCell<Pool<AsyncPgConnection>> = OnceCell::const_new();
-    POOL.get_or_init(async || create_pool(db_url).await.expect("error  creating pool"))
-        .await
+    POOL.get_or_init(async || {
+        create_pool(db_url, max_connections)
+            .await
+            .expect("error  creating pool")
+    })
+    .await
 }
 
 pub fn create_conection(db_url: &String) -> PgConnection {
     PgConnection::establish(db_url).unwrap_or_else(|_| panic!("Error connecting to {db_url}"))
 }
 
-pub async fn run_migrations(
-    connection: &mut PgConnection,
-) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    // This will run the necessary migrations.
-    //
-    // See the documentation for `MigrationHarness` for
-    // all available methods.
-    connection.run_pending_migrations(MIGRATIONS)?;
+/// Apply the embedded migrations the database is missing, returning the
+/// versions applied. Blocking, run it off the async runtime.
+pub fn run_migrations(db_url: &str) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let mut connection = PgConnection::establish(db_url)?;
+    let applied = connection.run_pending_migrations(MIGRATIONS)?;
+    Ok(applied.iter().map(ToString::to_string).collect())
+}
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[diesel(sql_type = Text)]
+    version: String,
+}
+
+/// Versions of the embedded migrations the database hasn't applied yet.
+pub async fn pending_migrations(
+    connection: &mut AsyncPgConnection,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let applied: HashSet<String> =
+        diesel::sql_query("SELECT version FROM __diesel_schema_migrations")
+            .load::<AppliedMigration>(connection)
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
 
-    Ok(())
+    let embedded = MigrationSource::<Pg>::migrations(&MIGRATIONS)?;
+    Ok(embedded
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .filter(|version| !applied.contains(version))
+        .collect())
 }
@@ -1,8 +1,20 @@
 #[cfg(feature = "ssr")]
 pub mod connection;
 
+#[cfg(feature = "ssr")]
+pub mod maintenance;
+
 #[cfg(feature = "ssr")]
 pub mod models;
 
+#[cfg(feature = "ssr")]
+pub mod rate_limits;
+
+#[cfg(feature = "ssr")]
+pub mod redirects;
+
 #[cfg(feature = "ssr")]
 pub mod schema;
+
+#[cfg(feature = "ssr")]
+pub mod short_links;
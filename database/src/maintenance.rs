@@ -0,0 +1,46 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::schema::maintenance_override;
+
+/// Whether an admin turned maintenance mode on or off, `None` while the
+/// configuration applies.
+pub async fn get(connection: &mut AsyncPgConnection) -> QueryResult<Option<bool>> {
+    maintenance_override::table
+        .select(maintenance_override::enabled)
+        .first(connection)
+        .await
+        .optional()
+}
+
+/// Override the configuration, recording the admin who did.
+pub async fn set(
+    connection: &mut AsyncPgConnection,
+    enabled: bool,
+    admin: &str,
+) -> QueryResult<()> {
+    diesel::insert_into(maintenance_override::table)
+        .values((
+            maintenance_override::id.eq(true),
+            maintenance_override::enabled.eq(enabled),
+            maintenance_override::set_by.eq(admin),
+        ))
+        .on_conflict(maintenance_override::id)
+        .do_update()
+        .set((
+            maintenance_override::enabled.eq(enabled),
+            maintenance_override::set_by.eq(admin),
+            maintenance_override::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(connection)
+        .await
+        .map(|_| ())
+}
+
+/// Go back to the configuration.
+pub async fn clear(connection: &mut AsyncPgConnection) -> QueryResult<()> {
+    diesel::delete(maintenance_override::table)
+        .execute(connection)
+        .await
+        .map(|_| ())
+}
@@ -1,6 +1,6 @@
 // src/models.rs
-use crate::schema::posts;
-use chrono::{DateTime, Utc};
+use crate::schema::{posts, redirects, short_link_clicks, short_links};
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -24,3 +24,53 @@ pub struct NewPost {
     pub content: String,
     pub user_id: Uuid,
 }
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = redirects)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Redirect {
+    pub id: i32,
+    pub source: String,
+    pub target: Option<String>,
+    pub status: i16,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = redirects)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewRedirect {
+    pub source: String,
+    pub target: Option<String>,
+    pub status: i16,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = short_links)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShortLink {
+    pub id: i32,
+    pub code: String,
+    pub post_slug: String,
+    pub vanity: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = short_links)]
+pub struct NewShortLink {
+    pub code: String,
+    pub post_slug: String,
+    pub vanity: bool,
+}
+
+/// Clicks on a short link in one day from one referring host.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = short_link_clicks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShortLinkClicks {
+    pub day: NaiveDate,
+    pub referrer: String,
+    pub clicks: i32,
+}
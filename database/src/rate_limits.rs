@@ -0,0 +1,32 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::schema::rate_limits;
+
+/// Count a request for `key` in the window starting at `window_start`,
+/// returning the requests counted in that window so far.
+pub async fn hit(
+    connection: &mut AsyncPgConnection,
+    key: &str,
+    window_start: i64,
+) -> QueryResult<i32> {
+    diesel::insert_into(rate_limits::table)
+        .values((
+            rate_limits::key.eq(key),
+            rate_limits::window_start.eq(window_start),
+            rate_limits::hits.eq(1),
+        ))
+        .on_conflict((rate_limits::key, rate_limits::window_start))
+        .do_update()
+        .set(rate_limits::hits.eq(rate_limits::hits + 1))
+        .returning(rate_limits::hits)
+        .get_result(connection)
+        .await
+}
+
+/// Delete the counts of windows that started before `window_start`.
+pub async fn prune(connection: &mut AsyncPgConnection, window_start: i64) -> QueryResult<usize> {
+    diesel::delete(rate_limits::table.filter(rate_limits::window_start.lt(window_start)))
+        .execute(connection)
+        .await
+}
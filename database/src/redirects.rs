@@ -0,0 +1,119 @@
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+use crate::models::{NewRedirect, Redirect};
+use crate::schema::{post_slugs, redirects, short_links};
+
+/// Every redirect, in source order.
+pub async fn list(connection: &mut AsyncPgConnection) -> QueryResult<Vec<Redirect>> {
+    redirects::table
+        .order(redirects::source)
+        .select(Redirect::as_select())
+        .load(connection)
+        .await
+}
+
+pub async fn create(
+    connection: &mut AsyncPgConnection,
+    redirect: &NewRedirect,
+) -> QueryResult<Redirect> {
+    diesel::insert_into(redirects::table)
+        .values(redirect)
+        .returning(Redirect::as_returning())
+        .get_result(connection)
+        .await
+}
+
+pub async fn update(
+    connection: &mut AsyncPgConnection,
+    id: i32,
+    redirect: &NewRedirect,
+) -> QueryResult<Redirect> {
+    diesel::update(redirects::table.find(id))
+        .set((redirect, redirects::updated_at.eq(diesel::dsl::now)))
+        .returning(Redirect::as_returning())
+        .get_result(connection)
+        .await
+}
+
+/// Delete a redirect, returning whether it existed.
+pub async fn delete(connection: &mut AsyncPgConnection, id: i32) -> QueryResult<bool> {
+    diesel::delete(redirects::table.find(id))
+        .execute(connection)
+        .await
+        .map(|deleted| deleted > 0)
+}
+
+/// `(original slug, current slug)` of every renamed post.
+pub async fn renamed_posts(
+    connection: &mut AsyncPgConnection,
+) -> QueryResult<Vec<(String, String)>> {
+    post_slugs::table
+        .select((post_slugs::original_slug, post_slugs::slug))
+        .load(connection)
+        .await
+}
+
+/// Give the post written as `original_slug`, now at `old_slug`, the slug
+/// `new_slug` and send its old URL to the new one with a `301`. Redirects
+/// that pointed at the old URL are moved along so they don't chain, and one
+/// for the new URL, left by an earlier rename, is dropped so it doesn't
+/// loop. The post's short links follow it too.
+pub async fn rename_post(
+    connection: &mut AsyncPgConnection,
+    original_slug: &str,
+    old_slug: &str,
+    new_slug: &str,
+) -> QueryResult<()> {
+    let old_path = format!("/blog/{old_slug}");
+    let new_path = format!("/blog/{new_slug}");
+    connection
+        .transaction(|connection| {
+            async move {
+                diesel::insert_into(post_slugs::table)
+                    .values((
+                        post_slugs::original_slug.eq(original_slug),
+                        post_slugs::slug.eq(new_slug),
+                    ))
+                    .on_conflict(post_slugs::original_slug)
+                    .do_update()
+                    .set((
+                        post_slugs::slug.eq(new_slug),
+                        post_slugs::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(connection)
+                    .await?;
+
+                diesel::update(short_links::table.filter(short_links::post_slug.eq(old_slug)))
+                    .set(short_links::post_slug.eq(new_slug))
+                    .execute(connection)
+                    .await?;
+                diesel::delete(redirects::table.filter(redirects::source.eq(&new_path)))
+                    .execute(connection)
+                    .await?;
+                diesel::update(redirects::table.filter(redirects::target.eq(&old_path)))
+                    .set((
+                        redirects::target.eq(&new_path),
+                        redirects::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(connection)
+                    .await?;
+                let redirect = NewRedirect {
+                    source: old_path.clone(),
+                    target: Some(new_path.clone()),
+                    status: 301,
+                };
+                diesel::insert_into(redirects::table)
+                    .values(&redirect)
+                    .on_conflict(redirects::source)
+                    .do_update()
+                    .set((&redirect, redirects::updated_at.eq(diesel::dsl::now)))
+                    .execute(connection)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+}
@@ -24,6 +24,71 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    redirects (id) {
+        id -> Int4,
+        source -> Varchar,
+        target -> Nullable<Varchar>,
+        status -> Int2,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    short_links (id) {
+        id -> Int4,
+        code -> Varchar,
+        post_slug -> Varchar,
+        vanity -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    short_link_clicks (short_link_id, day, referrer) {
+        short_link_id -> Int4,
+        day -> Date,
+        referrer -> Varchar,
+        clicks -> Int4,
+    }
+}
+
+diesel::table! {
+    maintenance_override (id) {
+        id -> Bool,
+        enabled -> Bool,
+        set_by -> Varchar,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    post_slugs (original_slug) {
+        original_slug -> Varchar,
+        slug -> Varchar,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    rate_limits (key, window_start) {
+        key -> Varchar,
+        window_start -> Int8,
+        hits -> Int4,
+    }
+}
+
 diesel::joinable!(posts -> users (user_id));
+diesel::joinable!(short_link_clicks -> short_links (short_link_id));
 
-diesel::allow_tables_to_appear_in_same_query!(users, posts,);
+diesel::allow_tables_to_appear_in_same_query!(
+    users,
+    posts,
+    maintenance_override,
+    post_slugs,
+    rate_limits,
+    redirects,
+    short_links,
+    short_link_clicks,
+);
@@ -0,0 +1,113 @@
+use chrono::Utc;
+use diesel::dsl::sum;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::models::{NewShortLink, ShortLink, ShortLinkClicks};
+use crate::schema::{short_link_clicks, short_links};
+
+/// Every short link, in code order.
+pub async fn list(connection: &mut AsyncPgConnection) -> QueryResult<Vec<ShortLink>> {
+    short_links::table
+        .order(short_links::code)
+        .select(ShortLink::as_select())
+        .load(connection)
+        .await
+}
+
+pub async fn find(
+    connection: &mut AsyncPgConnection,
+    code: &str,
+) -> QueryResult<Option<ShortLink>> {
+    short_links::table
+        .filter(short_links::code.eq(code))
+        .select(ShortLink::as_select())
+        .first(connection)
+        .await
+        .optional()
+}
+
+/// Slugs of the posts that have a generated code.
+pub async fn generated_slugs(connection: &mut AsyncPgConnection) -> QueryResult<Vec<String>> {
+    short_links::table
+        .filter(short_links::vanity.eq(false))
+        .select(short_links::post_slug)
+        .load(connection)
+        .await
+}
+
+pub async fn create(
+    connection: &mut AsyncPgConnection,
+    link: &NewShortLink,
+) -> QueryResult<ShortLink> {
+    diesel::insert_into(short_links::table)
+        .values(link)
+        .returning(ShortLink::as_returning())
+        .get_result(connection)
+        .await
+}
+
+/// Delete a short link and its clicks, returning whether it existed.
+pub async fn delete(connection: &mut AsyncPgConnection, id: i32) -> QueryResult<bool> {
+    diesel::delete(short_links::table.find(id))
+        .execute(connection)
+        .await
+        .map(|deleted| deleted > 0)
+}
+
+/// Count a click on the short link `id` today, from `referrer`.
+pub async fn record_click(
+    connection: &mut AsyncPgConnection,
+    id: i32,
+    referrer: &str,
+) -> QueryResult<()> {
+    diesel::insert_into(short_link_clicks::table)
+        .values((
+            short_link_clicks::short_link_id.eq(id),
+            short_link_clicks::day.eq(Utc::now().date_naive()),
+            short_link_clicks::referrer.eq(referrer),
+            short_link_clicks::clicks.eq(1),
+        ))
+        .on_conflict((
+            short_link_clicks::short_link_id,
+            short_link_clicks::day,
+            short_link_clicks::referrer,
+        ))
+        .do_update()
+        .set(short_link_clicks::clicks.eq(short_link_clicks::clicks + 1))
+        .execute(connection)
+        .await
+        .map(|_| ())
+}
+
+/// Clicks on the short link `id` by day and referrer, newest day first.
+pub async fn clicks(
+    connection: &mut AsyncPgConnection,
+    id: i32,
+) -> QueryResult<Vec<ShortLinkClicks>> {
+    short_link_clicks::table
+        .filter(short_link_clicks::short_link_id.eq(id))
+        .order((
+            short_link_clicks::day.desc(),
+            short_link_clicks::clicks.desc(),
+        ))
+        .select(ShortLinkClicks::as_select())
+        .load(connection)
+        .await
+}
+
+/// All time clicks of every short link that was clicked, by id.
+pub async fn total_clicks(connection: &mut AsyncPgConnection) -> QueryResult<Vec<(i32, i64)>> {
+    let totals: Vec<(i32, Option<i64>)> = short_link_clicks::table
+        .group_by(short_link_clicks::short_link_id)
+        .select((
+            short_link_clicks::short_link_id,
+            sum(short_link_clicks::clicks),
+        ))
+        .load(connection)
+        .await?;
+    Ok(totals
+        .into_iter()
+        .map(|(id, total)| (id, total.unwrap_or_default()))
+        .collect())
+}
@@ -0,0 +1,241 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use config::{AppConfig, Secret};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The user making a request, from a valid bearer token. The
+/// [`authenticate`] middleware adds it to the request extensions.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: String,
+    pub admin: bool,
+}
+
+#[derive(Deserialize)]
+struct TokenHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+    #[serde(default)]
+    admin: bool,
+}
+
+/// Verifies HS256 tokens against the current JWT secret and the previous
+/// ones still accepted after a rotation.
+pub struct Authenticator {
+    secrets: Vec<Secret<String>>,
+}
+
+impl Authenticator {
+    pub fn new(app: &AppConfig) -> Self {
+        Self {
+            secrets: app
+                .accepted_jwt_secrets()
+                .map(|secret| Secret::new(secret.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The user of an unexpired token signed with an accepted secret.
+    pub fn verify(&self, token: &str) -> Option<AuthUser> {
+        let mut parts = token.split('.');
+        let (Some(encoded_header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let header: TokenHeader = decode_json(encoded_header)?;
+        // Anything else, `none` in particular, is refused
+        if header.alg != "HS256" {
+            return None;
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let signed = &token[..encoded_header.len() + 1 + payload.len()];
+        let valid = self.secrets.iter().any(|secret| {
+            let Ok(mut mac) = HmacSha256::new_from_slice(secret.expose().as_bytes()) else {
+                return false;
+            };
+            mac.update(signed.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        });
+        if !valid {
+            return None;
+        }
+
+        let claims: Claims = decode_json(payload)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        (claims.exp > now).then_some(AuthUser {
+            id: claims.sub,
+            admin: claims.admin,
+        })
+    }
+
+    fn user_for(&self, headers: &HeaderMap) -> Option<AuthUser> {
+        let token = headers
+            .get(header::AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?;
+        self.verify(token.trim())
+    }
+}
+
+fn decode_json<T: for<'de> Deserialize<'de>>(part: &str) -> Option<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(part).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Middleware adding the [`AuthUser`] of a valid `Authorization: Bearer`
+/// token to the request. Requests with a missing or invalid token carry on
+/// as anonymous. The user is added to the response too, for the layers
+/// outside this one.
+pub async fn authenticate(
+    State(authenticator): State<Arc<Authenticator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let user = authenticator.user_for(request.headers());
+    if let Some(user) = &user {
+        request.extensions_mut().insert(user.clone());
+    }
+    let mut response = next.run(request).await;
+    if let Some(user) = user {
+        response.extensions_mut().insert(user);
+    }
+    response
+}
+
+/// Extractor for handlers only admins may call: `401 Unauthorized` without
+/// a valid token, `403 Forbidden` for other users.
+pub struct Admin(pub AuthUser);
+
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<AuthUser>() {
+            Some(user) if user.admin => Ok(Admin(user.clone())),
+            Some(_) => Err(StatusCode::FORBIDDEN),
+            None => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "current-secret-with-enough-length";
+    const PREVIOUS: &str = "previous-secret-with-enough-length";
+
+    fn authenticator() -> Authenticator {
+        Authenticator {
+            secrets: vec![
+                Secret::new(SECRET.to_string()),
+                Secret::new(PREVIOUS.to_string()),
+            ],
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_secs()
+    }
+
+    fn token(alg: &str, claims: &str, secret: &str) -> String {
+        let header = format!(r#"{{"alg":"{alg}","typ":"JWT"}}"#);
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("key");
+        mac.update(signed.as_bytes());
+        format!(
+            "{signed}.{}",
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    fn claims(exp: u64, admin: bool) -> String {
+        format!(r#"{{"sub":"frans","exp":{exp},"admin":{admin}}}"#)
+    }
+
+    #[test]
+    fn test_valid_tokens() {
+        let user = authenticator()
+            .verify(&token("HS256", &claims(now() + 60, true), SECRET))
+            .expect("current secret");
+        assert_eq!(user.id, "frans");
+        assert!(user.admin);
+
+        let user = authenticator()
+            .verify(&token("HS256", &claims(now() + 60, false), PREVIOUS))
+            .expect("previous secret");
+        assert!(!user.admin);
+
+        // Tokens from before the admin claim are regular users
+        let user = authenticator()
+            .verify(&token(
+                "HS256",
+                &format!(r#"{{"sub":"frans","exp":{}}}"#, now() + 60),
+                SECRET,
+            ))
+            .expect("no admin claim");
+        assert!(!user.admin);
+    }
+
+    #[test]
+    fn test_rejected_tokens() {
+        let authenticator = authenticator();
+        let valid = claims(now() + 60, true);
+
+        assert!(authenticator
+            .verify(&token("HS256", &valid, "some-other-secret"))
+            .is_none());
+        assert!(authenticator
+            .verify(&token("HS256", &claims(now() - 1, true), SECRET))
+            .is_none());
+        assert!(authenticator
+            .verify(&token("HS512", &valid, SECRET))
+            .is_none());
+
+        // `none` is refused with or without a signature
+        let signed = token("none", &valid, SECRET);
+        assert!(authenticator.verify(&signed).is_none());
+        let unsigned = signed.rsplit_once('.').expect("parts").0.to_string() + ".";
+        assert!(authenticator.verify(&unsigned).is_none());
+
+        // Claims swapped after signing
+        let user_token = token("HS256", &claims(now() + 60, false), SECRET);
+        let mut parts = user_token.split('.');
+        let (Some(header), Some(_), Some(signature)) = (parts.next(), parts.next(), parts.next())
+        else {
+            panic!("three parts");
+        };
+        let forged = format!("{header}.{}.{signature}", URL_SAFE_NO_PAD.encode(valid));
+        assert!(authenticator.verify(&forged).is_none());
+
+        assert!(authenticator.verify("not-a-token").is_none());
+        assert!(authenticator.verify("a.b.c.d").is_none());
+    }
+}
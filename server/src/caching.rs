@@ -0,0 +1,197 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use app::posts::{get_post, get_posts};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use config::CacheConfig;
+
+use crate::auth::AuthUser;
+
+/// Identifies the build, so every deploy invalidates the ETags of the last.
+const BUILD_ID: &str = concat!(env!("BUILD_GIT_SHA"), "@", env!("BUILD_TIMESTAMP"));
+
+/// Middleware applying the route's cache policy from `[cache]`. Pages it
+/// revalidates get a weak `ETag` from the build and the posts they show, and
+/// a matching `If-None-Match` is answered with `304 Not Modified`. Pages
+/// rendered for a signed in user are `private, no-store` without an `ETag`,
+/// shared caches must not hand them to anyone else.
+pub async fn cache_headers(
+    State(cache): State<Arc<CacheConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let policy = match *request.method() {
+        Method::GET | Method::HEAD => cache.policy_for(request.uri().path()),
+        _ => None,
+    };
+    let Some(policy) = policy else {
+        return next.run(request).await;
+    };
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let uri = request.uri().clone();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    if parts.extensions.get::<AuthUser>().is_some() {
+        parts.headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-store"),
+        );
+        parts.headers.remove(header::ETAG);
+        return Response::from_parts(parts, body);
+    }
+    if let Ok(value) = HeaderValue::from_str(&policy.cache_control()) {
+        parts.headers.insert(header::CACHE_CONTROL, value);
+    }
+    if !policy.uses_etag() || parts.headers.contains_key(header::ETAG) {
+        return Response::from_parts(parts, body);
+    }
+    let path_and_query = uri.path_and_query().map_or(uri.path(), |pq| pq.as_str());
+    let Some(etag) = etag(path_and_query) else {
+        return Response::from_parts(parts, body);
+    };
+
+    if if_none_match.is_some_and(|value| matches_etag(&value, &etag)) {
+        // Only validators and caching headers, a new CSP would not match the
+        // nonce in the page the browser already has. Dropping the body stops
+        // the render.
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        let headers = not_modified.headers_mut();
+        headers.insert(header::ETAG, etag);
+        for name in [header::CACHE_CONTROL, header::VARY] {
+            if let Some(value) = parts.headers.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        return not_modified;
+    }
+
+    parts.headers.insert(header::ETAG, etag);
+    Response::from_parts(parts, body)
+}
+
+/// Hash of the build, the page and the `updated_at` of the posts it shows,
+/// known without waiting for the streamed body. A post page shows its own
+/// post, any other page may list all of them.
+fn etag(path_and_query: &str) -> Option<HeaderValue> {
+    let mut hasher = DefaultHasher::new();
+    (BUILD_ID, path_and_query).hash(&mut hasher);
+    let path = path_and_query.split('?').next().unwrap_or_default();
+    let posts = match path.strip_prefix("/blog/").and_then(get_post) {
+        Some(post) => vec![post],
+        None => get_posts(),
+    };
+    for post in &posts {
+        (&post.slug, &post.updated_at).hash(&mut hasher);
+    }
+    HeaderValue::from_str(&format!("W/\"{:016x}\"", hasher.finish())).ok()
+}
+
+/// Weak comparison of an `If-None-Match` list against our tag.
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(candidates) = if_none_match.to_str() else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let ours = etag.to_str().map(opaque).unwrap_or_default();
+    candidates
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == ours)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use config::{CachePolicy, CacheRule};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_signed_in_pages_are_private() {
+        let cache = Arc::new(CacheConfig {
+            routes: vec![CacheRule {
+                pattern: "/".to_string(),
+                policy: CachePolicy::Public(60),
+            }],
+        });
+        // Stands in for `authenticate`, which runs inside this layer
+        let router = Router::new()
+            .route("/", get(|| async { "home" }))
+            .layer(axum::middleware::from_fn(
+                |request: Request, next: Next| async move {
+                    let signed_in = request.headers().contains_key(header::AUTHORIZATION);
+                    let mut response = next.run(request).await;
+                    if signed_in {
+                        response.extensions_mut().insert(AuthUser {
+                            id: "user".to_string(),
+                            admin: false,
+                        });
+                    }
+                    response
+                },
+            ))
+            .layer(axum::middleware::from_fn_with_state(cache, cache_headers));
+
+        let anonymous = Request::get("/").body(Body::empty()).expect("request");
+        let response = router.clone().oneshot(anonymous).await.expect("response");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        let etag = response.headers()[header::ETAG].clone();
+
+        let signed_in = Request::get("/")
+            .header(header::AUTHORIZATION, "Bearer token")
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .expect("request");
+        let response = router.oneshot(signed_in).await.expect("response");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "private, no-store"
+        );
+        assert!(!response.headers().contains_key(header::ETAG));
+    }
+
+    #[test]
+    fn test_etag() {
+        let slug = &get_posts()[0].slug;
+        let post = etag(&format!("/blog/{slug}")).expect("etag");
+        assert!(post.to_str().expect("ascii").starts_with("W/\""));
+        assert_eq!(Some(&post), etag(&format!("/blog/{slug}")).as_ref());
+        assert_ne!(Some(&post), etag("/blog").as_ref());
+        assert_ne!(etag("/blog"), etag("/blog?page=2"));
+    }
+
+    #[test]
+    fn test_matches_etag() {
+        let ours = HeaderValue::from_static("W/\"0123456789abcdef\"");
+        for candidates in [
+            "W/\"0123456789abcdef\"",
+            "\"0123456789abcdef\"",
+            "\"other\", W/\"0123456789abcdef\"",
+            "*",
+        ] {
+            assert!(
+                matches_etag(&HeaderValue::from_static(candidates), &ours),
+                "{candidates}"
+            );
+        }
+        for candidates in ["W/\"fedcba9876543210\"", "\"other\", \"more\"", ""] {
+            assert!(
+                !matches_etag(&HeaderValue::from_static(candidates), &ours),
+                "{candidates}"
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use config::{Config, Layers, ServerConfig, FIELDS};
+
+/// Portfolio web server.
+#[derive(Debug, Parser)]
+#[command(name = "server", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (the default).
+    Serve,
+    /// Inspect the configuration without starting the server.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Write brotli and gzip copies of the WASM, JS and CSS bundles, run
+    /// after `cargo leptos build --release`.
+    Precompress {
+        /// Site directory to compress.
+        #[arg(default_value = "target/site")]
+        dir: PathBuf,
+    },
+    /// Render every page into a directory that any static host can serve,
+    /// run after `cargo leptos build --release`.
+    Export {
+        /// Directory to write the site to, it must be empty or missing.
+        #[arg(long, default_value = "dist")]
+        out: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Load and validate the configuration, exiting non-zero on problems.
+    Check,
+    /// Print the effective configuration and where each value came from.
+    Print {
+        /// Print secrets in clear text, they are redacted otherwise.
+        #[arg(long)]
+        show_secrets: bool,
+    },
+}
+
+/// `server config check`
+pub fn check() -> ExitCode {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(err) = crate::leptos_options(&config.server) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    println!(
+        "Configuration is valid ({} environment, listening on {})",
+        config.app.environment.profile(),
+        config.bind_address()
+    );
+    ExitCode::SUCCESS
+}
+
+/// `server config print`
+///
+/// Values are printed as TOML with their source in a trailing comment. An
+/// invalid configuration is still printed, followed by its problems, so it
+/// can be debugged.
+pub fn print(show_secrets: bool) -> ExitCode {
+    let layers = Layers::discover();
+
+    let mut section = "";
+    for field in FIELDS {
+        let (field_section, key) = field.path.split_once('.').unwrap_or(("", field.path));
+        if field_section != section {
+            if !section.is_empty() {
+                println!();
+            }
+            println!("[{field_section}]");
+            section = field_section;
+        }
+
+        let value = if show_secrets {
+            layers.get(field.env).map(str::to_string)
+        } else {
+            layers.redacted(field.env)
+        };
+        match (value, layers.source(field.env)) {
+            (Some(value), Some(source)) => {
+                let reloadable = if field.reloadable { ", reloadable" } else { "" };
+                println!(
+                    "{key} = {}  # {} from {source}{reloadable}",
+                    field.toml_value(&value),
+                    field.env
+                )
+            }
+            _ => println!("# {key} is not set ({})", field.env),
+        }
+    }
+
+    match Config::from_layers(&layers) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("\n{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `server precompress`
+pub fn precompress(dir: &std::path::Path) -> ExitCode {
+    match crate::precompress::precompress(dir) {
+        Ok(summary) => {
+            println!(
+                "Compressed {} files in {}: {} bytes, {} with brotli, {} with gzip",
+                summary.files,
+                dir.display(),
+                summary.original_bytes,
+                summary.brotli_bytes,
+                summary.gzip_bytes
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to compress {}: {err}", dir.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `server export`
+///
+/// Only the `[server]` settings are needed, so no database or secrets have
+/// to be configured.
+pub async fn export(out: &std::path::Path) -> ExitCode {
+    let options = ServerConfig::from_layers(&Layers::discover())
+        .map_err(|err| err.to_string())
+        .and_then(|server| crate::leptos_options(&server));
+    let options = match options {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    app::rendering::images::set_image_root(options.site_root.as_ref());
+
+    match crate::export::export(options, out).await {
+        Ok(summary) => {
+            println!(
+                "Exported {} pages and {} files to {}",
+                summary.pages,
+                summary.files,
+                out.display()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Export failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
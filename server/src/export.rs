@@ -0,0 +1,352 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+
+use app::posts::get_posts;
+use app::rendering::images::IMAGE_ROUTE;
+use app::{shell, App};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use leptos::prelude::LeptosOptions;
+use leptos_axum::{generate_route_list, LeptosRoutes};
+use thiserror::Error;
+use tower::ServiceExt;
+
+use crate::og::OgImages;
+
+/// Path no route answers, rendered to `404.html` for the static host.
+const NOT_FOUND_PATH: &str = "/404.html";
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("{0} already exists and is not empty")]
+    OutputNotEmpty(PathBuf),
+
+    #[error("route {0} has a parameter the export can't fill in, only :slug is known")]
+    UnknownParameter(String),
+
+    #[error("failed to render {path}: {reason}")]
+    Render { path: String, reason: String },
+
+    #[error("broken internal links:{}", list_links(.0))]
+    BrokenLinks(Vec<BrokenLink>),
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug)]
+pub struct BrokenLink {
+    pub path: String,
+    /// Page the link was found on.
+    pub on: String,
+    pub status: StatusCode,
+}
+
+fn list_links(links: &[BrokenLink]) -> String {
+    links
+        .iter()
+        .map(|link| format!("\n  - {} on {} ({})", link.path, link.on, link.status))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub pages: usize,
+    pub files: usize,
+}
+
+/// Render the site into `out` for a static host: the site root with its
+/// bundles, one `index.html` per page and the Open Graph cards of every
+/// post.
+///
+/// Pages are found from the route list, with `:slug` filled in for every
+/// post, and by following the internal links of every exported page. A link
+/// to anything that can't be exported fails the export.
+///
+/// Static hosts ignore query strings, so the resized variants behind
+/// `/img/...?w=` all fall back to a copy of the original image. The AVIF and
+/// WebP `<source>`s of pictures are removed, they would point browsers at
+/// that copy under the wrong type.
+///
+/// The app has no tag pages or feeds yet, so there are none to export.
+pub async fn export(options: LeptosOptions, out: &Path) -> Result<ExportSummary, ExportError> {
+    if std::fs::read_dir(out).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(ExportError::OutputNotEmpty(out.to_path_buf()));
+    }
+    let site_root = PathBuf::from(options.site_root.as_ref());
+    let mut summary = ExportSummary {
+        files: copy_dir(&site_root, out)?,
+        ..Default::default()
+    };
+
+    let routes = generate_route_list(App);
+    let mut queue = VecDeque::new();
+    for route in &routes {
+        for path in expand(route.path())? {
+            queue.push_back((path, "the route list".to_string()));
+        }
+    }
+    for post in get_posts() {
+        queue.push_back((
+            format!("/og/{}.png", post.slug),
+            "the post list".to_string(),
+        ));
+    }
+
+    let router = Router::new()
+        .merge(OgImages::new(&site_root).router())
+        .leptos_routes(&options, routes, {
+            let options = options.clone();
+            move || shell(options.clone())
+        })
+        .fallback(crate::fileserv::file_and_error_handler::<LeptosOptions, _>(
+            shell,
+        ))
+        .with_state(options);
+
+    let mut seen = HashSet::new();
+    let mut broken = Vec::new();
+    while let Some((path, on)) = queue.pop_front() {
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let Some(relative) = relative_path(&path) else {
+            broken.push(BrokenLink {
+                path,
+                on,
+                status: StatusCode::BAD_REQUEST,
+            });
+            continue;
+        };
+        if out.join(&relative).is_file() {
+            continue;
+        }
+        if let Some(image) = path.strip_prefix(&format!("{IMAGE_ROUTE}/")) {
+            let source = site_root.join(image);
+            if !source.is_file() {
+                broken.push(BrokenLink {
+                    path,
+                    on,
+                    status: StatusCode::NOT_FOUND,
+                });
+                continue;
+            }
+            let target = out.join(&relative);
+            create_parent(&target)?;
+            std::fs::copy(source, target)?;
+            summary.files += 1;
+            continue;
+        }
+
+        let (status, html, body) = render(&router, &path).await?;
+        if status != StatusCode::OK {
+            broken.push(BrokenLink { path, on, status });
+            continue;
+        }
+        let (target, body) = if html {
+            let page = strip_sources(&String::from_utf8_lossy(&body));
+            for link in internal_links(&page) {
+                queue.push_back((link, path.clone()));
+            }
+            summary.pages += 1;
+            (out.join(&relative).join("index.html"), page.into_bytes())
+        } else {
+            summary.files += 1;
+            (out.join(&relative), body)
+        };
+        create_parent(&target)?;
+        std::fs::write(target, body)?;
+    }
+    if !broken.is_empty() {
+        return Err(ExportError::BrokenLinks(broken));
+    }
+
+    let (_, _, not_found) = render(&router, NOT_FOUND_PATH).await?;
+    std::fs::write(out.join(NOT_FOUND_PATH.trim_start_matches('/')), not_found)?;
+
+    Ok(summary)
+}
+
+/// Concrete paths of a route, one per post for routes taking a `slug`.
+fn expand(route: &str) -> Result<Vec<String>, ExportError> {
+    let is_parameter = |segment: &str| segment.starts_with(':') || segment.starts_with('{');
+    if !route.split('/').any(is_parameter) {
+        return Ok(vec![route.to_string()]);
+    }
+    let unknown = route
+        .split('/')
+        .any(|segment| is_parameter(segment) && !matches!(segment, ":slug" | "{slug}"));
+    if unknown {
+        return Err(ExportError::UnknownParameter(route.to_string()));
+    }
+    Ok(get_posts()
+        .into_iter()
+        .map(|post| {
+            route
+                .split('/')
+                .map(|segment| {
+                    if is_parameter(segment) {
+                        post.slug.as_str()
+                    } else {
+                        segment
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect())
+}
+
+/// Status, whether the response is HTML, and the body of `path`.
+async fn render(router: &Router, path: &str) -> Result<(StatusCode, bool, Vec<u8>), ExportError> {
+    let error = |reason: String| ExportError::Render {
+        path: path.to_string(),
+        reason,
+    };
+    let request = Request::get(path)
+        .body(Body::empty())
+        .map_err(|err| error(err.to_string()))?;
+    let response = router
+        .clone()
+        .oneshot(request)
+        .await
+        .map_err(|err| error(err.to_string()))?;
+
+    let status = response.status();
+    let html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|err| error(err.to_string()))?;
+    Ok((status, html, body.to_vec()))
+}
+
+/// Paths of the `href`, `src` and `srcset` URLs on this site, without their
+/// query and fragment.
+fn internal_links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    for attribute in ["href=\"", "src=\"", "srcset=\""] {
+        for (at, _) in html.match_indices(attribute) {
+            let value = &html[at + attribute.len()..];
+            let Some(end) = value.find('"') else {
+                continue;
+            };
+            let urls = value[..end]
+                .split(',')
+                .filter_map(|candidate| candidate.split_whitespace().next());
+            for url in urls {
+                if url.starts_with('/') && !url.starts_with("//") {
+                    let path = url.split(['?', '#']).next().unwrap_or_default();
+                    links.push(path.to_string());
+                }
+            }
+        }
+    }
+    links
+}
+
+/// `html` without its `<source>` elements, leaving pictures to their `<img>`.
+fn strip_sources(html: &str) -> String {
+    let mut stripped = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<source") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find('>') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// `path` relative to the output directory, `None` if it tries to leave it.
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(path.trim_start_matches('/'));
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then_some(relative)
+}
+
+fn create_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/// Copy every file under `from` into `to`, returning how many were copied.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<usize> {
+    std::fs::create_dir_all(to)?;
+    let mut copied = 0;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copied += copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_links() {
+        let html = r#"<a href="/blog?page=2#top">Blog</a>
+            <a href="https://example.com/">Elsewhere</a>
+            <a href="//cdn.example.com/x.js">CDN</a>
+            <img src="/img/a.png?w=640" srcset="/img/a.png?w=640 640w, /img/a.png?w=1280 1280w"/>"#;
+        assert_eq!(
+            internal_links(html),
+            vec!["/blog", "/img/a.png", "/img/a.png", "/img/a.png"]
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("/blog").expect("static route"), vec!["/blog"]);
+        let slugs: Vec<String> = get_posts()
+            .into_iter()
+            .map(|post| format!("/blog/{}", post.slug))
+            .collect();
+        assert_eq!(expand("/blog/:slug").expect("slug route"), slugs);
+        assert_eq!(expand("/blog/{slug}").expect("slug route"), slugs);
+        assert!(matches!(
+            expand("/tags/:tag"),
+            Err(ExportError::UnknownParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("/blog/post"),
+            Some(PathBuf::from("blog/post"))
+        );
+        assert_eq!(relative_path("/"), Some(PathBuf::new()));
+        assert_eq!(relative_path("/blog/../../etc/passwd"), None);
+        assert_eq!(relative_path("/./blog"), None);
+    }
+
+    #[test]
+    fn test_strip_sources() {
+        let html = r#"<picture><source type="image/avif" srcset="/img/a.png?fmt=avif"/><source type="image/webp" srcset="/img/a.png?fmt=webp"><img src="/img/a.png"/></picture>"#;
+        assert_eq!(
+            strip_sources(html),
+            r#"<picture><img src="/img/a.png"/></picture>"#
+        );
+    }
+}
@@ -1,44 +1,131 @@
-use app::App;
+use std::future::Future;
+use std::pin::Pin;
+
 use axum::response::Response as AxumResponse;
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, Response, StatusCode, Uri},
+    extract::{FromRef, State},
+    http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode, Uri},
     response::IntoResponse,
 };
 use leptos::prelude::*;
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
 
-pub async fn file_and_error_handler(
-    uri: Uri,
-    State(options): State<LeptosOptions>,
-    req: Request<Body>,
-) -> AxumResponse {
-    let root = options.site_root.clone();
-    let res = get_static_file(uri.clone(), &root).await.unwrap();
-
-    if res.status() == StatusCode::OK {
-        res.into_response()
-    } else {
-        let handler =
-            leptos_axum::render_app_to_stream(options.to_owned(), move || view! { <App/> });
-        handler(req).await.into_response()
+/// Fallback for everything that is not a Leptos route: serve the file from
+/// the site root if there is one, otherwise render the app so its not-found
+/// page is shown with a 404 status. Any other answer about a file, like a
+/// `304 Not Modified` or a `206 Partial Content`, is passed on as is.
+pub fn file_and_error_handler<S, IV>(
+    shell: impl Fn(LeptosOptions) -> IV + Clone + Send + Sync + 'static,
+) -> impl Fn(Uri, State<S>, Request<Body>) -> Pin<Box<dyn Future<Output = AxumResponse> + Send>>
+       + Clone
+       + Send
+       + 'static
+where
+    IV: IntoView + 'static,
+    S: Send + Sync + Clone + 'static,
+    LeptosOptions: FromRef<S>,
+{
+    move |uri: Uri, State(state): State<S>, req: Request<Body>| {
+        let shell = shell.clone();
+        Box::pin(async move {
+            let options = LeptosOptions::from_ref(&state);
+
+            match get_static_file(uri, req.headers(), &options.site_root).await {
+                Ok(res) if res.status() != StatusCode::NOT_FOUND => res.into_response(),
+                Ok(_) => {
+                    let handler = leptos_axum::render_app_to_stream(move || shell(options.clone()));
+                    let mut res = handler(req).await.into_response();
+                    // The app sets its own status for error pages, only fill in
+                    // the 404 when it didn't
+                    if res.status() == StatusCode::OK {
+                        *res.status_mut() = StatusCode::NOT_FOUND;
+                    }
+                    res
+                }
+                Err((status, message)) => {
+                    tracing::error!("failed to serve static file: {message}");
+                    (status, "Internal Server Error").into_response()
+                }
+            }
+        })
     }
 }
 
-async fn get_static_file(uri: Uri, root: &str) -> Result<Response<Body>, (StatusCode, String)> {
-    let req = Request::builder()
+async fn get_static_file(
+    uri: Uri,
+    headers: &HeaderMap,
+    root: &str,
+) -> Result<Response<Body>, (StatusCode, String)> {
+    let mut req = Request::builder()
         .uri(uri.clone())
         .body(Body::empty())
-        .unwrap();
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Invalid request for {uri}: {err}"),
+            )
+        })?;
+    // Keep `Accept-Encoding` and the conditional headers of the original
+    // request
+    *req.headers_mut() = headers.clone();
     // `ServeDir` implements `tower::Service` so we can call it with `tower::ServiceExt::oneshot`
     // This path is relative to the cargo root
-    match ServeDir::new(root).oneshot(req).await {
-        Ok(res) => Ok(res.map(Body::new)),
+    // Bundles have `.br`/`.gz` siblings written by `server precompress`
+    let serve_dir = ServeDir::new(root).precompressed_br().precompressed_gzip();
+    match serve_dir.oneshot(req).await {
+        Ok(mut res) => {
+            // The compression layer skips encoded responses, so it doesn't
+            // add the `Vary` shared caches need to tell the variants apart
+            if res.headers().contains_key(header::CONTENT_ENCODING) {
+                res.headers_mut()
+                    .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            }
+            Ok(res.map(Body::new))
+        }
         Err(err) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Something went wrong: {err}"),
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use app::shell;
+
+    #[tokio::test]
+    async fn test_conditional_get_of_static_file() {
+        let dir = std::env::temp_dir().join(format!("fileserv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        std::fs::write(dir.join("logo.png"), b"not really a png").expect("logo.png");
+        let options = LeptosOptions::builder()
+            .output_name("test")
+            .site_root(dir.to_string_lossy().as_ref())
+            .build();
+        let handler = file_and_error_handler::<LeptosOptions, _>(shell);
+
+        let request = |if_modified_since: Option<&'static str>| {
+            let mut request = Request::get("/logo.png");
+            if let Some(since) = if_modified_since {
+                request = request.header(header::IF_MODIFIED_SINCE, since);
+            }
+            request.body(Body::empty()).expect("request")
+        };
+        let uri = Uri::from_static("/logo.png");
+        let fresh = handler(uri.clone(), State(options.clone()), request(None)).await;
+        let revalidated = handler(
+            uri,
+            State(options),
+            request(Some("Fri, 01 Jan 2100 00:00:00 GMT")),
+        )
+        .await;
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{FromRef, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use database::connection::{acquire, pending_migrations, DbConnection, DbPool};
+use serde::Serialize;
+
+/// How long readiness waits for a database connection.
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `/healthz`, `/readyz` and `/version`, routed ahead of the Leptos app.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    DbPool: FromRef<S>,
+{
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz::<S>))
+        .route("/version", get(version))
+}
+
+/// Liveness: the process is up and answering requests.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    database: String,
+    migrations: String,
+}
+
+/// Readiness: a database connection can be acquired and every embedded
+/// migration has been applied.
+async fn readyz<S>(State(state): State<S>) -> impl IntoResponse
+where
+    DbPool: FromRef<S>,
+{
+    let pool = DbPool::from_ref(&state);
+    let not_checked = || Err("not checked".to_string());
+    let (database, migrations) = match tokio::time::timeout(ACQUIRE_TIMEOUT, acquire(&pool)).await {
+        Ok(Ok(mut connection)) => (
+            Ok("ok".to_string()),
+            check_migrations(&mut connection).await,
+        ),
+        Ok(Err(err)) => (Err(err.to_string()), not_checked()),
+        Err(_) => (
+            Err("timed out acquiring a connection".to_string()),
+            not_checked(),
+        ),
+    };
+
+    let ready = database.is_ok() && migrations.is_ok();
+    if !ready {
+        tracing::warn!("Not ready: database {database:?}, migrations {migrations:?}");
+    }
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = Readiness {
+        ready,
+        database: database.unwrap_or_else(|err| err),
+        migrations: migrations.unwrap_or_else(|err| err),
+    };
+    (status, Json(body))
+}
+
+async fn check_migrations(connection: &mut DbConnection<'_>) -> Result<String, String> {
+    match pending_migrations(connection).await {
+        Ok(pending) if pending.is_empty() => Ok("current".to_string()),
+        Ok(pending) => Err(format!("pending: {}", pending.join(", "))),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+struct Version {
+    version: &'static str,
+    git_sha: &'static str,
+    built_at: &'static str,
+}
+
+async fn version() -> Json<Version> {
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BUILD_GIT_SHA"),
+        built_at: env!("BUILD_TIMESTAMP"),
+    })
+}
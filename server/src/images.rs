@@ -0,0 +1,349 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use app::rendering::images::{ImageFormat, IMAGE_ROUTE, MAX_WIDTH, SMALL_WIDTHS, WIDTHS};
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use image::{imageops::FilterType, DynamicImage, ImageReader, Limits};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+const WEBP_QUALITY: f32 = 75.0;
+const AVIF_QUALITY: u8 = 75;
+
+#[derive(Debug, Error)]
+pub enum ImageError {
+    #[error("image not found")]
+    NotFound,
+
+    #[error("invalid image request: {0}")]
+    BadRequest(String),
+
+    #[error("source image too large: {0}")]
+    TooLarge(String),
+
+    #[error("failed to process image: {0}")]
+    Processing(String),
+
+    #[error("image cache error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl IntoResponse for ImageError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ImageError::NotFound => StatusCode::NOT_FOUND,
+            ImageError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ImageError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ImageError::Processing(_) | ImageError::Io(_) => {
+                tracing::error!("{self}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Resizes local images to WebP/AVIF variants and caches them on disk.
+#[derive(Clone, Debug)]
+pub struct ImagePipeline {
+    /// Directory source images are read from, usually the site root.
+    pub source_root: PathBuf,
+    /// Directory generated variants are written to.
+    pub cache_dir: PathBuf,
+    /// Sources larger than this many bytes are rejected.
+    pub max_source_bytes: u64,
+    /// Sources with more pixels than this are rejected before decoding.
+    pub max_source_pixels: u64,
+    /// Encodes running at once, one per core. Misses past that wait their
+    /// turn instead of piling up on the blocking pool.
+    encodes: Arc<Semaphore>,
+}
+
+impl ImagePipeline {
+    pub fn new(source_root: impl Into<PathBuf>) -> Self {
+        Self {
+            source_root: source_root.into(),
+            cache_dir: PathBuf::from("target/image-cache"),
+            max_source_bytes: 20 * 1024 * 1024,
+            max_source_pixels: 40_000_000,
+            encodes: Arc::new(Semaphore::new(
+                std::thread::available_parallelism().map_or(1, usize::from),
+            )),
+        }
+    }
+
+    /// Router serving `/img/{*path}?w=<width>&fmt=<webp|avif>`.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route(&format!("{IMAGE_ROUTE}/{{*path}}"), get(image_handler))
+            .with_state(Arc::new(self))
+    }
+
+    /// Return the encoded variant, generating and caching it if needed.
+    pub async fn variant(
+        &self,
+        path: &str,
+        width: Option<u32>,
+        format: ImageFormat,
+    ) -> Result<Vec<u8>, ImageError> {
+        let source = self.source_path(path)?;
+        let metadata = tokio::fs::metadata(&source)
+            .await
+            .map_err(|_| ImageError::NotFound)?;
+        if !metadata.is_file() {
+            return Err(ImageError::NotFound);
+        }
+        if metadata.len() > self.max_source_bytes {
+            return Err(ImageError::TooLarge(format!(
+                "{} bytes exceeds the {} byte limit",
+                metadata.len(),
+                self.max_source_bytes
+            )));
+        }
+
+        let width = width.map(normalize_width);
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        (path, modified, metadata.len(), width, format.extension()).hash(&mut hasher);
+        let cached =
+            self.cache_dir
+                .join(format!("{:016x}.{}", hasher.finish(), format.extension()));
+
+        if let Ok(bytes) = tokio::fs::read(&cached).await {
+            crate::metrics::record_cache("image", true);
+            return Ok(bytes);
+        }
+        crate::metrics::record_cache("image", false);
+
+        let _permit = self
+            .encodes
+            .acquire()
+            .await
+            .map_err(|e| ImageError::Processing(e.to_string()))?;
+        // Another request may have encoded it while this one waited
+        if let Ok(bytes) = tokio::fs::read(&cached).await {
+            return Ok(bytes);
+        }
+
+        let max_pixels = self.max_source_pixels;
+        let bytes = tokio::task::spawn_blocking(move || encode(&source, width, format, max_pixels))
+            .await
+            .map_err(|e| ImageError::Processing(e.to_string()))??;
+
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        write_cache_file(&cached, &bytes).await?;
+
+        Ok(bytes)
+    }
+
+    fn source_path(&self, path: &str) -> Result<PathBuf, ImageError> {
+        let relative = Path::new(path);
+        if !app::rendering::images::is_resizable(&format!("/{path}"))
+            || relative
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(ImageError::BadRequest(format!("unsupported path: {path}")));
+        }
+        Ok(self.source_root.join(relative))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageQuery {
+    w: Option<u32>,
+    fmt: Option<String>,
+}
+
+async fn image_handler(
+    UrlPath(path): UrlPath<String>,
+    Query(query): Query<ImageQuery>,
+    State(pipeline): State<Arc<ImagePipeline>>,
+) -> Result<Response, ImageError> {
+    let format = match query.fmt.as_deref() {
+        Some(fmt) => fmt.parse().map_err(ImageError::BadRequest)?,
+        None => ImageFormat::Webp,
+    };
+    let bytes = pipeline.variant(&path, query.w, format).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.mime_type()),
+            (header::CACHE_CONTROL, "public, max-age=604800"),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Write `bytes` to `path` through a temporary file of its own, so readers
+/// never see a partially written file and concurrent writers of the same
+/// file don't interleave. The last rename wins, every writer has the same
+/// bytes.
+pub(crate) async fn write_cache_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+
+    let partial = path.with_extension(format!(
+        "{}-{}.partial",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    tokio::fs::write(&partial, bytes).await?;
+    if let Err(e) = tokio::fs::rename(&partial, path).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Round a requested width up to the next width of the `srcset` ladder, or
+/// of the small widths below it, so each image has a handful of variants at
+/// most.
+fn normalize_width(width: u32) -> u32 {
+    SMALL_WIDTHS
+        .into_iter()
+        .chain(WIDTHS)
+        .find(|step| *step >= width)
+        .unwrap_or(MAX_WIDTH)
+}
+
+fn encode(
+    source: &Path,
+    width: Option<u32>,
+    format: ImageFormat,
+    max_pixels: u64,
+) -> Result<Vec<u8>, ImageError> {
+    let processing = |e: image::ImageError| ImageError::Processing(e.to_string());
+
+    let (source_width, source_height) = ImageReader::open(source)?
+        .with_guessed_format()?
+        .into_dimensions()
+        .map_err(processing)?;
+    let pixels = u64::from(source_width) * u64::from(source_height);
+    if pixels > max_pixels {
+        return Err(ImageError::TooLarge(format!(
+            "{source_width}x{source_height} exceeds the {max_pixels} pixel limit"
+        )));
+    }
+
+    let mut reader = ImageReader::open(source)?.with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(source_width);
+    limits.max_image_height = Some(source_height);
+    reader.limits(limits);
+    let image = reader.decode().map_err(processing)?;
+
+    // Never upscale, only shrink to the requested width
+    let target = width.unwrap_or(MAX_WIDTH).min(source_width);
+    let image = if target < source_width {
+        image.resize(target, u32::MAX, FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let image = image.to_rgba8();
+
+    match format {
+        // The `image` crate only encodes lossless WebP, which comes out
+        // larger than the JPEG it was made from
+        ImageFormat::Webp => {
+            let encoder = webp::Encoder::from_rgba(&image, image.width(), image.height());
+            Ok(encoder.encode(WEBP_QUALITY).to_vec())
+        }
+        ImageFormat::Avif => {
+            let mut bytes = Cursor::new(Vec::new());
+            DynamicImage::ImageRgba8(image)
+                .write_with_encoder(image::codecs::avif::AvifEncoder::new_with_speed_quality(
+                    &mut bytes,
+                    8,
+                    AVIF_QUALITY,
+                ))
+                .map_err(processing)?;
+            Ok(bytes.into_inner())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_path() {
+        let pipeline = ImagePipeline::new("site");
+        assert_eq!(
+            pipeline.source_path("posts/cover.png").expect("valid path"),
+            Path::new("site/posts/cover.png")
+        );
+        for path in [
+            "../secret.png",
+            "posts/../../secret.png",
+            "./cover.png",
+            "/etc/cover.png",
+            "posts/notes.txt",
+            "posts/cover.svg",
+        ] {
+            assert!(
+                matches!(pipeline.source_path(path), Err(ImageError::BadRequest(_))),
+                "{path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_cache_file() {
+        let dir = std::env::temp_dir().join(format!("images-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.expect("create dir");
+        let path = dir.join("variant.webp");
+
+        let mut writes = tokio::task::JoinSet::new();
+        for i in 0..8u8 {
+            let path = path.clone();
+            writes.spawn(async move { write_cache_file(&path, &[i; 1024]).await });
+        }
+        while let Some(result) = writes.join_next().await {
+            result.expect("join").expect("write");
+        }
+
+        let bytes = tokio::fs::read(&path).await.expect("read");
+        assert_eq!(bytes.len(), 1024);
+        assert!(bytes.iter().all(|byte| *byte == bytes[0]));
+        let mut entries = tokio::fs::read_dir(&dir).await.expect("read dir");
+        let mut files = 0;
+        while entries.next_entry().await.expect("entry").is_some() {
+            files += 1;
+        }
+        assert_eq!(files, 1, "temporary files are left behind");
+        tokio::fs::remove_dir_all(&dir).await.expect("remove dir");
+    }
+
+    #[test]
+    fn test_normalize_width() {
+        assert_eq!(normalize_width(0), 40);
+        assert_eq!(normalize_width(40), 40);
+        assert_eq!(normalize_width(41), 80);
+        assert_eq!(normalize_width(80), 80);
+        assert_eq!(normalize_width(100), 160);
+        assert_eq!(normalize_width(161), 320);
+        assert_eq!(normalize_width(320), 320);
+        assert_eq!(normalize_width(321), 640);
+        assert_eq!(normalize_width(1000), 1280);
+        assert_eq!(normalize_width(1920), 1920);
+        assert_eq!(normalize_width(u32::MAX), MAX_WIDTH);
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{HeaderName, Request, Response},
+    Router,
+};
+use config::LogFormat;
+use tower::ServiceBuilder;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::Span;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Changes the log level of the running subscriber.
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Install the global subscriber. `log` records from dependencies are
+/// forwarded to it as well.
+pub fn init(format: LogFormat, level: LevelFilter) -> Result<LevelHandle, String> {
+    let (filter, handle) = reload::Layer::new(level);
+    // The Postgres driver logs every statement with its parameters, our own
+    // query log covers that without the bind values
+    let dependencies = Targets::new()
+        .with_default(LevelFilter::TRACE)
+        .with_target("tokio_postgres", LevelFilter::INFO);
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(dependencies);
+    let result = match format {
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_span_list(false),
+            )
+            .try_init(),
+        LogFormat::Pretty => registry
+            .with(fmt::layer().with_ansi(std::io::stdout().is_terminal()))
+            .try_init(),
+    };
+    result
+        .map(|()| handle)
+        .map_err(|err| format!("Failed to initialise logging: {err}"))
+}
+
+/// Give every request an `X-Request-Id`, keeping the one set by a proxy,
+/// and run it in a span carrying the id, method and path. The id is echoed
+/// back in the response.
+pub fn trace_requests<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::new(REQUEST_ID, MakeRequestUuid))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(request_span)
+                    .on_request(())
+                    .on_response(log_response)
+                    .on_failure(()),
+            )
+            .layer(PropagateRequestIdLayer::new(REQUEST_ID)),
+    )
+}
+
+fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        request_id,
+    )
+}
+
+fn log_response(response: &Response<Body>, latency: Duration, span: &Span) {
+    // Nothing to report on when the level filters out the request span
+    if span.is_disabled() {
+        return;
+    }
+    let status = response.status().as_u16();
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    if response.status().is_server_error() {
+        tracing::error!(status, latency_ms, "request failed");
+    } else {
+        tracing::info!(status, latency_ms, "request finished");
+    }
+}
@@ -1,37 +1,299 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
 use app::*;
-use axum::Router;
-use config::CONFIG;
-use database::connection::{create_conection, run_migrations};
-use leptos::logging::log;
+use axum::{middleware, Router};
+use clap::Parser;
+use config::{Config, ServerConfig};
+use database::connection::{create_pool, run_migrations};
 use leptos::prelude::*;
 use leptos_axum::{generate_route_list, LeptosRoutes};
+use tower_http::compression::CompressionLayer;
+
+mod auth;
+mod caching;
+mod cli;
+mod export;
+mod fileserv;
+mod health;
+mod images;
+mod logging;
+mod maintenance;
+mod metrics;
+mod og;
+mod page_cache;
+mod precompress;
+mod rate_limit;
+mod redirects;
+mod runtime;
+mod security;
+mod short_links;
+mod shutdown;
+mod state;
+
+use auth::Authenticator;
+use cli::{Cli, Command, ConfigCommand};
+use images::ImagePipeline;
+use maintenance::Maintenance;
+use metrics::MetricsEndpoint;
+use og::OgImages;
+use page_cache::PageCache;
+use rate_limit::RateLimiter;
+use redirects::Redirects;
+use runtime::Reloader;
+use security::SecurityHeaders;
+use short_links::ShortLinks;
+use state::AppState;
 
 #[tokio::main]
-async fn main() {
-    let conf = get_configuration(None).unwrap();
-    let addr = conf.leptos_options.site_addr;
-    let leptos_options = conf.leptos_options;
-    // log!("Running migrations ...");
-    // let mut connection = create_conection(&CONFIG.database.url);
-    // run_migrations(&mut connection).await.unwrap();
-    // log!("Migrations run successfully");
+async fn main() -> ExitCode {
+    match Cli::parse().command {
+        None | Some(Command::Serve) => serve().await,
+        Some(Command::Config(ConfigCommand::Check)) => cli::check(),
+        Some(Command::Config(ConfigCommand::Print { show_secrets })) => cli::print(show_secrets),
+        Some(Command::Precompress { dir }) => cli::precompress(&dir),
+        Some(Command::Export { out }) => cli::export(&out).await,
+    }
+}
+
+async fn serve() -> ExitCode {
+    let (config, sources) = match Config::load_with_sources() {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let (reloader, settings) = Reloader::new(&config, sources.clone());
+    let log_level = match logging::init(config.app.log_format, settings.borrow().log_level) {
+        Ok(handle) => handle,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    runtime::follow_log_level(settings.clone(), log_level);
+    for (key, source) in sources.report() {
+        tracing::debug!("config {key} from {source}");
+    }
+    reloader.spawn();
+
+    let leptos_options = match leptos_options(&config.server) {
+        Ok(options) => options,
+        Err(err) => {
+            tracing::error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let addr = leptos_options.site_addr;
+    app::rendering::images::set_image_root(leptos_options.site_root.as_ref());
+
+    let metrics_handle = match metrics::install() {
+        Ok(handle) => handle,
+        Err(err) => {
+            tracing::error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    tracing::info!("Connecting to {}", config.database.redacted_url());
+    let pool = match create_pool(
+        config.database.url.expose(),
+        config.database.max_connections,
+    )
+    .await
+    {
+        Ok(pool) => pool,
+        Err(err) => {
+            tracing::error!("Failed to create the database pool: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // The tables have to exist before redirects and short links load below
+    let url = config.database.url.expose().to_string();
+    match tokio::task::spawn_blocking(move || run_migrations(&url)).await {
+        Ok(Ok(applied)) => {
+            for version in applied {
+                tracing::info!("Applied migration {version}");
+            }
+        }
+        Ok(Err(err)) => {
+            tracing::error!("Failed to run the migrations: {err}");
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            tracing::error!("Failed to run the migrations: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
 
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
+    let ssr_routes: HashSet<String> = routes
+        .iter()
+        .map(|route| route.path().to_string())
+        .collect();
 
+    let page_cache = PageCache::new(config.page_cache.clone());
+    page_cache.watch_posts();
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.clone(),
+        settings.clone(),
+        pool.clone(),
+    ));
+    // Background tasks holding the pool, stopped before it is closed
+    let mut tasks = vec![rate_limiter.spawn_pruning()];
+    let redirects = Redirects::new(pool.clone());
+    redirects.reload().await;
+    tasks.push(redirects.spawn_refresh());
+    let short_links = ShortLinks::new(pool.clone());
+    tasks.push(short_links.spawn_generate_missing());
+    let maintenance = Maintenance::new(settings.clone(), pool.clone());
+    maintenance.refresh().await;
+    tasks.push(maintenance.spawn_refresh());
+
+    let state = AppState {
+        leptos_options: leptos_options.clone(),
+        pool: pool.clone(),
+        settings,
+        page_cache: page_cache.clone(),
+    };
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
+        .merge(ImagePipeline::new(leptos_options.site_root.as_ref()).router())
+        .merge(OgImages::new(leptos_options.site_root.as_ref()).router())
+        .merge(redirects.clone().router())
+        .merge(short_links.router())
+        .merge(maintenance.clone().router())
+        .leptos_routes(&state, routes, {
             let leptos_options = leptos_options.clone();
             move || shell(leptos_options.clone())
         })
-        .fallback(leptos_axum::file_and_error_handler(shell))
-        .with_state(leptos_options);
+        .fallback(fileserv::file_and_error_handler::<AppState, _>(shell))
+        .layer(middleware::from_fn_with_state(
+            page_cache.clone(),
+            page_cache::serve_cached,
+        ))
+        // Outside the page cache, a new rule applies to cached pages at once
+        .layer(middleware::from_fn_with_state(
+            redirects,
+            redirects::redirect,
+        ))
+        .layer(middleware::from_fn_with_state(
+            rate_limiter,
+            rate_limit::limit,
+        ))
+        // Inside authentication so admins get through
+        .layer(middleware::from_fn_with_state(
+            maintenance,
+            maintenance::guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(Authenticator::new(&config.app)),
+            auth::authenticate,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(ssr_routes),
+            metrics::track_requests,
+        ));
+    // Health checks and metrics are routed after the tracing and metrics
+    // layers are applied so scrapes and probes don't count as traffic
+    let mut app = logging::trace_requests(app)
+        .merge(health::router())
+        .merge(SecurityHeaders::router());
+
+    let metrics_endpoint =
+        MetricsEndpoint::new(metrics_handle, pool.clone(), config.metrics.token.clone());
+    match (&config.metrics.addr, &config.metrics.token) {
+        (Some(addr), _) => match metrics::spawn_listener(addr, metrics_endpoint).await {
+            Ok(listener) => tasks.push(listener),
+            Err(err) => {
+                tracing::error!("{err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, Some(_)) => app = app.merge(metrics_endpoint.router()),
+        (None, None) => {
+            tracing::info!("Metrics endpoint disabled, set METRICS_ADDR or METRICS_TOKEN")
+        }
+    }
+    let security = SecurityHeaders::new(config.security.csp_report_only, &leptos_options);
+    let app = app
+        .layer(middleware::from_fn_with_state(
+            Arc::new(config.cache.clone()),
+            caching::cache_headers,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(security),
+            security::add_headers,
+        ))
+        // Pages and API responses, static bundles are already compressed
+        // on disk and pass through untouched
+        .layer(CompressionLayer::new().no_deflate().no_zstd())
+        .with_state(state);
+    page_cache.set_renderer(app.clone());
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
-    log!("listening on http://{}", &addr);
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    tracing::info!("listening on http://{}", &addr);
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("Failed to bind {addr}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let drain_timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+    let result = shutdown::serve(listener, app, drain_timeout).await;
+    page_cache.stop_regenerating();
+    for task in tasks {
+        task.abort();
+        // Only returns once the task is dropped, along with its pool handle
+        let _ = task.await;
+    }
+
+    // Connections close once the last handle to the pool is gone. Requests
+    // cut off by the drain timeout still hold theirs until the runtime shuts
+    // down right after.
+    let pool_state = pool.state();
+    tracing::info!(
+        "Closing the database pool ({} connections, {} idle)",
+        pool_state.connections,
+        pool_state.idle_connections
+    );
+    drop(pool);
+
+    let code = match result {
+        Ok(()) => {
+            tracing::info!("Server stopped");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            tracing::error!("Server error: {err}");
+            ExitCode::FAILURE
+        }
+    };
+    // Logs are written straight to stdout, make sure nothing is left in
+    // its buffer
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    code
+}
+
+/// Build the Leptos options from `Cargo.toml` metadata, with the output
+/// name, site root, pkg dir and address taken from our configuration.
+fn leptos_options(server: &ServerConfig) -> Result<LeptosOptions, String> {
+    let conf = get_configuration(None).map_err(|e| format!("Invalid Leptos configuration: {e}"))?;
+    let site_addr: SocketAddr = server
+        .bind_address()
+        .parse()
+        .map_err(|e| format!("Invalid bind address {}: {e}", server.bind_address()))?;
+
+    let mut options = conf.leptos_options;
+    options.output_name = server.leptos_output_name.as_str().into();
+    options.site_root = server.leptos_site_root.as_str().into();
+    options.site_pkg_dir = server.leptos_site_pkg_dir.as_str().into();
+    options.site_addr = site_addr;
+    Ok(options)
 }
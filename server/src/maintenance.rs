@@ -0,0 +1,221 @@
+use std::fmt::Display;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use database::connection::{acquire, DbPool};
+use database::maintenance as store;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::auth::{Admin, AuthUser};
+use crate::runtime::Settings;
+
+/// How often the admin override is read again, to pick up changes made on
+/// other machines.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Self-contained so it renders without the site's assets.
+const PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Down for Maintenance | Frans Ramirez Neyra</title>
+<style>
+body { margin: 0; min-height: 100vh; display: flex; align-items: center; justify-content: center; background: #111827; color: #d1d5db; font-family: system-ui, sans-serif; text-align: center; }
+h1 { font-family: "Courier New", monospace; font-size: 2.25rem; color: #f87171; margin: 0 0 1rem; }
+p { margin: 0 0 2rem; padding: 0 1rem; }
+a { display: inline-block; padding: 0.75rem 1.5rem; border-radius: 9999px; background: linear-gradient(to right, #9333ea, #0891b2); color: #fff; font-weight: 600; text-decoration: none; }
+</style>
+</head>
+<body>
+<main>
+<h1>503 - Down for Maintenance</h1>
+<p>The site is being worked on and will be back shortly.</p>
+<a href="/">Try Again</a>
+</main>
+</body>
+</html>
+"#;
+
+/// Maintenance mode from `runtime.maintenance`, which admins can override.
+/// The override is kept in the database so every machine follows it, each
+/// within [`REFRESH_INTERVAL`].
+#[derive(Clone)]
+pub struct Maintenance {
+    settings: Settings,
+    pool: DbPool,
+    /// Set through `/admin/maintenance`, wins over the configuration. A copy
+    /// of the database row so requests never wait on it.
+    forced: Arc<RwLock<Option<bool>>>,
+}
+
+#[derive(Serialize)]
+struct Status {
+    enabled: bool,
+    /// Whether an admin overrode the configuration.
+    forced: bool,
+    retry_after_secs: u64,
+}
+
+#[derive(Deserialize)]
+struct Toggle {
+    enabled: bool,
+}
+
+impl Maintenance {
+    pub fn new(settings: Settings, pool: DbPool) -> Self {
+        Self {
+            settings,
+            pool,
+            forced: Arc::default(),
+        }
+    }
+
+    /// Read the override again. On failure the current one is kept.
+    pub async fn refresh(&self) {
+        let loaded = match acquire(&self.pool).await {
+            Ok(mut connection) => store::get(&mut connection)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        match loaded {
+            Ok(forced) => self.force(forced),
+            Err(err) => {
+                tracing::warn!("Keeping the maintenance override, failed to load it: {err}")
+            }
+        }
+    }
+
+    pub fn spawn_refresh(&self) -> JoinHandle<()> {
+        let maintenance = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+            // The first tick fires right away, the override was just loaded
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                maintenance.refresh().await;
+            }
+        })
+    }
+
+    fn forced(&self) -> Option<bool> {
+        *self.forced.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn force(&self, enabled: Option<bool>) {
+        *self.forced.write().unwrap_or_else(PoisonError::into_inner) = enabled;
+    }
+
+    fn enabled(&self) -> bool {
+        self.forced()
+            .unwrap_or_else(|| self.settings.borrow().runtime.maintenance)
+    }
+
+    fn retry_after_secs(&self) -> u64 {
+        self.settings.borrow().runtime.maintenance_retry_after_secs
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            enabled: self.enabled(),
+            forced: self.forced().is_some(),
+            retry_after_secs: self.retry_after_secs(),
+        }
+    }
+
+    /// Admin API at `/admin/maintenance`: `GET` the status, `PUT`
+    /// `{"enabled": bool}` to override the configuration and `DELETE` to go
+    /// back to it.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route("/admin/maintenance", get(status).put(toggle).delete(reset))
+            .with_state(self)
+    }
+}
+
+/// Middleware answering `503 Service Unavailable` with a `Retry-After`
+/// while maintenance mode is on. Admins get through to check on the site,
+/// health checks are routed outside of it.
+pub async fn guard(
+    State(maintenance): State<Maintenance>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let admin = request
+        .extensions()
+        .get::<AuthUser>()
+        .is_some_and(|user| user.admin);
+    if admin || !maintenance.enabled() {
+        return next.run(request).await;
+    }
+
+    let wants_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"));
+    let (content_type, body) = if wants_html {
+        ("text/html; charset=utf-8", PAGE)
+    } else {
+        ("text/plain; charset=utf-8", "Down for maintenance")
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [
+            (
+                header::RETRY_AFTER,
+                maintenance.retry_after_secs().to_string(),
+            ),
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn status(State(maintenance): State<Maintenance>, _: Admin) -> Json<Status> {
+    Json(maintenance.status())
+}
+
+async fn toggle(
+    State(maintenance): State<Maintenance>,
+    Admin(admin): Admin,
+    Json(toggle): Json<Toggle>,
+) -> Result<Json<Status>, StatusCode> {
+    let mut connection = acquire(&maintenance.pool).await.map_err(store_failed)?;
+    store::set(&mut connection, toggle.enabled, &admin.id)
+        .await
+        .map_err(store_failed)?;
+    maintenance.force(Some(toggle.enabled));
+    tracing::warn!(admin = %admin.id, enabled = toggle.enabled, "maintenance mode set");
+    Ok(Json(maintenance.status()))
+}
+
+async fn reset(
+    State(maintenance): State<Maintenance>,
+    Admin(admin): Admin,
+) -> Result<Json<Status>, StatusCode> {
+    let mut connection = acquire(&maintenance.pool).await.map_err(store_failed)?;
+    store::clear(&mut connection).await.map_err(store_failed)?;
+    maintenance.force(None);
+    tracing::warn!(admin = %admin.id, "maintenance mode follows the configuration again");
+    Ok(Json(maintenance.status()))
+}
+
+fn store_failed(err: impl Display) -> StatusCode {
+    tracing::error!("Failed to store the maintenance override: {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use config::Secret;
+use database::connection::DbPool;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tokio::task::JoinHandle;
+
+use crate::page_cache::{CACHE_HIT, CACHE_STALE, CACHE_STATUS_HEADER};
+
+/// Histogram buckets, in seconds, for every `*_seconds` metric.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Where Leptos mounts server functions.
+const SERVER_FN_PREFIX: &str = "/api/";
+
+/// Install the global Prometheus recorder.
+pub fn install() -> Result<PrometheusHandle, String> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &LATENCY_BUCKETS)
+        .and_then(PrometheusBuilder::install_recorder)
+        .map_err(|err| format!("Failed to install the metrics recorder: {err}"))
+}
+
+/// Count a lookup in one of the caches.
+pub fn record_cache(cache: &'static str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    counter!("cache_requests_total", "cache" => cache, "result" => result).increment(1);
+}
+
+/// Serves `/metrics` in the Prometheus text format.
+#[derive(Clone)]
+pub struct MetricsEndpoint {
+    handle: PrometheusHandle,
+    pool: DbPool,
+    token: Option<Secret<String>>,
+}
+
+impl MetricsEndpoint {
+    pub fn new(handle: PrometheusHandle, pool: DbPool, token: Option<Secret<String>>) -> Self {
+        MetricsEndpoint {
+            handle,
+            pool,
+            token,
+        }
+    }
+
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route("/metrics", get(render))
+            .with_state(Arc::new(self))
+    }
+}
+
+async fn render(State(endpoint): State<Arc<MetricsEndpoint>>, headers: HeaderMap) -> Response {
+    if let Some(token) = &endpoint.token {
+        let sent = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !constant_time_eq(sent.as_bytes(), token.expose().as_bytes()) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+            )
+                .into_response();
+        }
+    }
+
+    // Pool gauges are sampled on scrape rather than tracked on every change
+    let pool = endpoint.pool.state();
+    gauge!("db_pool_connections").set(f64::from(pool.connections));
+    gauge!("db_pool_idle_connections").set(f64::from(pool.idle_connections));
+
+    endpoint.handle.run_upkeep();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        endpoint.handle.render(),
+    )
+        .into_response()
+}
+
+/// Serve the endpoint on its own listener, away from public traffic.
+pub async fn spawn_listener(
+    addr: &str,
+    endpoint: MetricsEndpoint,
+) -> Result<JoinHandle<()>, String> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|err| format!("Failed to bind the metrics listener {addr}: {err}"))?;
+    tracing::info!("metrics on http://{addr}/metrics");
+    Ok(tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, endpoint.router()).await {
+            tracing::error!("Metrics listener error: {err}");
+        }
+    }))
+}
+
+/// Compare without returning early, so the token can't be guessed from the
+/// response time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Whether the page cache answered, a stale copy included.
+fn page_cache_hit(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CACHE_STATUS_HEADER)
+        .is_some_and(|status| status == CACHE_HIT || status == CACHE_STALE)
+}
+
+/// Request count and latency by route template. Leptos page routes also
+/// feed the SSR histogram and server function routes their call and error
+/// counters. `ssr_routes` holds the paths of the Leptos pages.
+///
+/// Latencies end when the response headers are ready. Pages stream their
+/// body after that, so the SSR histogram is time to first byte rather than
+/// the whole render.
+pub async fn track_requests(
+    State(ssr_routes): State<Arc<HashSet<String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    // Templates keep the label set bounded, unmatched paths share one label
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+
+    let response = next.run(request).await;
+    let elapsed = started.elapsed().as_secs_f64();
+    let status = response.status();
+
+    let label = route.clone().unwrap_or_else(|| "fallback".to_string());
+    counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => label.clone(),
+        "status" => status.as_u16().to_string(),
+    )
+    .increment(1);
+    histogram!("http_request_duration_seconds", "method" => method, "route" => label)
+        .record(elapsed);
+
+    match route {
+        // Pages served from the page cache were not rendered
+        Some(route) if ssr_routes.contains(&route) && !page_cache_hit(&response) => {
+            histogram!("ssr_request_duration_seconds", "route" => route).record(elapsed);
+        }
+        Some(route) if route.starts_with(SERVER_FN_PREFIX) => {
+            counter!("server_fn_calls_total", "function" => route.clone()).increment(1);
+            if status.is_client_error() || status.is_server_error() {
+                counter!("server_fn_errors_total", "function" => route).increment(1);
+            }
+        }
+        _ => {}
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page_cache::{CACHE_BYPASS, CACHE_MISS};
+
+    #[test]
+    fn test_page_cache_hit() {
+        let response = |status: &'static str| {
+            let mut response = Response::new(axum::body::Body::empty());
+            response.headers_mut().insert(
+                CACHE_STATUS_HEADER,
+                axum::http::HeaderValue::from_static(status),
+            );
+            response
+        };
+        assert!(page_cache_hit(&response(CACHE_HIT)));
+        assert!(page_cache_hit(&response(CACHE_STALE)));
+        assert!(!page_cache_hit(&response(CACHE_MISS)));
+        assert!(!page_cache_hit(&response(CACHE_BYPASS)));
+        assert!(!page_cache_hit(&Response::new(axum::body::Body::empty())));
+    }
+}
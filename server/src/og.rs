@@ -0,0 +1,295 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use app::posts::{get_post, BlogPostData};
+use app::rendering::escape_html;
+use app::site::OG_IMAGE_SIZE;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use resvg::{tiny_skia, usvg};
+use thiserror::Error;
+
+use crate::images::write_cache_file;
+
+const WIDTH: u32 = OG_IMAGE_SIZE.0;
+const HEIGHT: u32 = OG_IMAGE_SIZE.1;
+/// Bump when the card template changes so cached cards are regenerated.
+const TEMPLATE_VERSION: u32 = 1;
+const TITLE_LINE_CHARS: usize = 26;
+const TITLE_MAX_LINES: usize = 3;
+/// Logo drawn on every card, relative to the site root.
+const LOGO: &str = "logo.png";
+
+#[derive(Debug, Error)]
+pub enum OgError {
+    #[error("post not found")]
+    NotFound,
+
+    #[error("failed to render card: {0}")]
+    Render(String),
+
+    #[error("card cache error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl IntoResponse for OgError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            OgError::NotFound => StatusCode::NOT_FOUND,
+            OgError::Render(_) | OgError::Io(_) => {
+                tracing::error!("{self}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Renders branded Open Graph cards for blog posts and caches them on disk
+/// by content hash.
+#[derive(Clone)]
+pub struct OgImages {
+    /// Directory the logo and other template assets are read from.
+    site_root: PathBuf,
+    cache_dir: PathBuf,
+    fonts: Arc<usvg::fontdb::Database>,
+}
+
+impl OgImages {
+    pub fn new(site_root: impl Into<PathBuf>) -> Self {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+
+        Self {
+            site_root: site_root.into(),
+            cache_dir: PathBuf::from("target/og-cache"),
+            fonts: Arc::new(fonts),
+        }
+    }
+
+    /// Router serving `/og/{slug}.png`.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route("/og/{file}", get(og_handler))
+            .with_state(Arc::new(self))
+    }
+
+    /// Return the PNG card for `post`, rendering it on a cache miss.
+    pub async fn card(&self, post: &BlogPostData) -> Result<Vec<u8>, OgError> {
+        let svg = card_svg(post);
+        // A new logo changes every card without changing the template
+        let logo = tokio::fs::metadata(self.site_root.join(LOGO)).await.ok();
+        let logo_modified = logo
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        let logo_len = logo.as_ref().map(|metadata| metadata.len());
+        let mut hasher = DefaultHasher::new();
+        (TEMPLATE_VERSION, logo_modified, logo_len, &svg).hash(&mut hasher);
+        let cached = self.cache_dir.join(format!("{:016x}.png", hasher.finish()));
+
+        if let Ok(bytes) = tokio::fs::read(&cached).await {
+            crate::metrics::record_cache("og", true);
+            return Ok(bytes);
+        }
+        crate::metrics::record_cache("og", false);
+
+        let renderer = self.clone();
+        let bytes = tokio::task::spawn_blocking(move || renderer.rasterize(&svg))
+            .await
+            .map_err(|e| OgError::Render(e.to_string()))??;
+
+        // Another request may have rendered it in the meantime
+        if let Ok(bytes) = tokio::fs::read(&cached).await {
+            return Ok(bytes);
+        }
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        write_cache_file(&cached, &bytes).await?;
+
+        Ok(bytes)
+    }
+
+    fn rasterize(&self, svg: &str) -> Result<Vec<u8>, OgError> {
+        let options = usvg::Options {
+            resources_dir: Some(self.site_root.clone()),
+            fontdb: self.fonts.clone(),
+            ..usvg::Options::default()
+        };
+        let tree =
+            usvg::Tree::from_str(svg, &options).map_err(|e| OgError::Render(e.to_string()))?;
+        let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT)
+            .ok_or_else(|| OgError::Render("invalid canvas size".to_string()))?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+        pixmap
+            .encode_png()
+            .map_err(|e| OgError::Render(e.to_string()))
+    }
+}
+
+async fn og_handler(
+    Path(file): Path<String>,
+    State(images): State<Arc<OgImages>>,
+) -> Result<Response, OgError> {
+    let post = file
+        .strip_suffix(".png")
+        .and_then(get_post)
+        .ok_or(OgError::NotFound)?;
+    let bytes = images.card(&post).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// SVG template of the card: the starfield from `Background`, the logo,
+/// category pill, wrapped title and date.
+fn card_svg(post: &BlogPostData) -> String {
+    // Same star layout as the `Background` component, scaled to the card
+    let stars: String = (0..50)
+        .map(|i| {
+            format!(
+                r#"<circle cx="{}" cy="{}" r="{}" fill="white" opacity="{:.1}"/>"#,
+                (i * 17) % 100 * WIDTH as usize / 100,
+                (i * 23) % 100 * HEIGHT as usize / 100,
+                1 + (i % 3),
+                0.3 + (i % 7) as f32 * 0.1,
+            )
+        })
+        .collect();
+
+    let title: String = wrap_title(&post.title)
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                r#"<tspan x="80" dy="{}">{}</tspan>"#,
+                if i == 0 { 0 } else { 76 },
+                escape_html(line)
+            )
+        })
+        .collect();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">
+  <defs>
+    <linearGradient id="surface" x1="0" y1="0" x2="1" y2="1">
+      <stop offset="0%" stop-color="#0f1419"/>
+      <stop offset="50%" stop-color="#1e293b"/>
+      <stop offset="100%" stop-color="#0f172a"/>
+    </linearGradient>
+    <linearGradient id="accent" x1="0" y1="0" x2="1" y2="0">
+      <stop offset="0%" stop-color="#22d3ee"/>
+      <stop offset="100%" stop-color="#a855f7"/>
+    </linearGradient>
+  </defs>
+  <rect width="100%" height="100%" fill="url(#surface)"/>
+  {stars}
+  <rect x="40" y="40" width="{card_width}" height="{card_height}" rx="24" fill="#0f172a" fill-opacity="0.7" stroke="#6366f1" stroke-opacity="0.4" stroke-width="2"/>
+  <image x="80" y="80" width="72" height="72" xlink:href="{LOGO}"/>
+  <rect x="176" y="96" width="{pill_width}" height="40" rx="20" fill="#a855f7" fill-opacity="0.2" stroke="#a855f7" stroke-opacity="0.5"/>
+  <text x="196" y="123" font-family="DejaVu Sans, sans-serif" font-size="22" fill="#22d3ee">{category}</text>
+  <text x="80" y="250" font-family="Courier New, DejaVu Sans Mono, monospace" font-size="64" font-weight="bold" fill="url(#accent)">{title}</text>
+  <rect x="80" y="510" width="128" height="4" fill="url(#accent)"/>
+  <text x="80" y="560" font-family="DejaVu Sans, sans-serif" font-size="26" fill="#cbd5e1">Frans Ramirez Neyra · {date} · {read_time}</text>
+</svg>"##,
+        card_width = WIDTH - 80,
+        card_height = HEIGHT - 80,
+        pill_width = 40 + post.category.chars().count() * 13,
+        category = escape_html(&post.category),
+        date = escape_html(&post.date),
+        read_time = escape_html(&post.read_time),
+    )
+}
+
+/// Greedy word wrap for the monospace title, truncated with an ellipsis.
+fn wrap_title(title: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in title.split_whitespace() {
+        if !current.is_empty()
+            && current.chars().count() + 1 + word.chars().count() > TITLE_LINE_CHARS
+        {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > TITLE_MAX_LINES {
+        lines.truncate(TITLE_MAX_LINES);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_title() {
+        assert_eq!(wrap_title("Short title"), vec!["Short title"]);
+        assert_eq!(
+            wrap_title("Custom fields and layouts using Tanstack Forms"),
+            vec!["Custom fields and layouts", "using Tanstack Forms"]
+        );
+
+        // A word longer than a line gets a line of its own
+        let long = "x".repeat(TITLE_LINE_CHARS + 4);
+        assert_eq!(
+            wrap_title(&format!("A {long} word")),
+            vec!["A".to_string(), long, "word".to_string()]
+        );
+
+        let lines = wrap_title(&"word ".repeat(40));
+        assert_eq!(lines.len(), TITLE_MAX_LINES);
+        assert!(lines[..TITLE_MAX_LINES - 1]
+            .iter()
+            .all(|line| line.chars().count() <= TITLE_LINE_CHARS));
+        assert!(lines[TITLE_MAX_LINES - 1].ends_with("word…"));
+
+        // Exactly the line limit is not truncated
+        let lines = wrap_title(&"wordwordwordwordwordword ".repeat(TITLE_MAX_LINES));
+        assert_eq!(lines.len(), TITLE_MAX_LINES);
+        assert!(!lines[TITLE_MAX_LINES - 1].ends_with('…'));
+    }
+
+    #[test]
+    fn test_card_svg_escapes_title() {
+        let post = BlogPostData {
+            slug: "escaping".to_string(),
+            title: "Rust & <Leptos>".to_string(),
+            excerpt: String::new(),
+            category: "rust".to_string(),
+            date: "2024-12-15".to_string(),
+            updated_at: "2024-12-15".to_string(),
+            read_time: "1 min read".to_string(),
+            content: String::new(),
+        };
+        let svg = card_svg(&post);
+        assert!(svg.contains("Rust &amp; &lt;Leptos&gt;"), "{svg}");
+        assert!(!svg.contains("<Leptos>"));
+        assert!(!svg.contains("Rust & "));
+    }
+}
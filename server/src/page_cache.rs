@@ -0,0 +1,461 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use app::csp::NONCE_HEADER;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use config::PageCacheConfig;
+use leptos::nonce::Nonce;
+use tower::ServiceExt;
+
+use crate::auth::AuthUser;
+
+/// Response header telling whether the page came from the cache, one of
+/// the `CACHE_*` values below.
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+/// Served from the cache.
+pub const CACHE_HIT: &str = "HIT";
+/// Served from the cache while a fresh copy renders.
+pub const CACHE_STALE: &str = "STALE";
+/// Rendered, and cached if it can be.
+pub const CACHE_MISS: &str = "MISS";
+/// Rendered for a signed in user, never cached.
+pub const CACHE_BYPASS: &str = "BYPASS";
+
+/// Query parameters the pages read. Any other is left out of the cache key,
+/// so made up parameters can't fill the cache with copies of one page.
+const PAGE_PARAMS: &[&str] = &[];
+
+/// Marks the requests the cache makes itself to render a page again.
+#[derive(Clone)]
+struct Regeneration;
+
+enum Lookup {
+    Fresh(CachedPage),
+    /// Past its TTL but still within the stale window.
+    Stale(CachedPage),
+    Missing,
+}
+
+#[derive(Clone)]
+struct CachedPage {
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+}
+
+/// Rendered pages kept in memory, keyed by path and query, so anonymous
+/// visitors don't wait on SSR for pages that rarely change.
+///
+/// Pages past their TTL keep being served while they render again in the
+/// background, until the stale window runs out too. Pages showing a post
+/// are regenerated as soon as [`app::posts::post_changed`] reports it,
+/// which a rename through the admin API does.
+#[derive(Clone)]
+pub struct PageCache {
+    config: Arc<PageCacheConfig>,
+    pages: Arc<Mutex<HashMap<String, CachedPage>>>,
+    /// Keys being rendered in the background, so each renders once at a
+    /// time.
+    regenerating: Arc<Mutex<HashSet<String>>>,
+    /// The whole app, to render pages outside of a visitor's request.
+    renderer: Arc<Mutex<Option<Router>>>,
+}
+
+impl PageCache {
+    pub fn new(config: PageCacheConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            pages: Arc::default(),
+            regenerating: Arc::default(),
+            renderer: Arc::default(),
+        }
+    }
+
+    /// Render background regenerations with `app`, the router this cache
+    /// is layered on. Without it expired pages are rendered on request.
+    pub fn set_renderer(&self, app: Router) {
+        *self.renderer() = Some(app);
+    }
+
+    /// Let go of the app on shutdown. It holds this cache and the database
+    /// pool, which would otherwise never be dropped.
+    pub fn stop_regenerating(&self) {
+        self.renderer().take();
+    }
+
+    fn renderer(&self) -> MutexGuard<'_, Option<Router>> {
+        self.renderer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.ttl_secs)
+    }
+
+    /// A poisoned lock only means a request panicked while holding it, the
+    /// map itself is still usable.
+    fn pages(&self) -> MutexGuard<'_, HashMap<String, CachedPage>> {
+        self.pages.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lookup(&self, key: &str) -> Lookup {
+        let ttl = self.ttl();
+        let stale = ttl + Duration::from_secs(self.config.stale_secs);
+        let mut pages = self.pages();
+        let Some(page) = pages.get(key) else {
+            return Lookup::Missing;
+        };
+        let age = page.stored_at.elapsed();
+        if age < ttl {
+            Lookup::Fresh(page.clone())
+        } else if age < stale && self.renderer().is_some() {
+            Lookup::Stale(page.clone())
+        } else {
+            pages.remove(key);
+            Lookup::Missing
+        }
+    }
+
+    fn insert(&self, key: String, page: CachedPage) {
+        let ttl = self.ttl() + Duration::from_secs(self.config.stale_secs);
+        let mut pages = self.pages();
+        let full = |pages: &HashMap<String, CachedPage>| {
+            pages.len() >= self.config.max_entries && !pages.contains_key(&key)
+        };
+        if full(&pages) {
+            pages.retain(|_, page| page.stored_at.elapsed() < ttl);
+        }
+        while full(&pages) {
+            let Some(oldest) = pages
+                .iter()
+                .min_by_key(|(_, page)| page.stored_at)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            pages.remove(&oldest);
+        }
+        pages.insert(key, page);
+    }
+
+    /// Render `key` again in the background and replace the cached copy,
+    /// unless that is already under way.
+    fn regenerate(&self, key: String) {
+        let Some(renderer) = self.renderer().clone() else {
+            return;
+        };
+        if !self.regenerating().insert(key.clone()) {
+            return;
+        }
+        let cache = self.clone();
+        tokio::spawn(async move {
+            // Layers further out, like the rate limiter, expect the address
+            // of a client
+            let request = Request::get(&key)
+                .extension(Regeneration)
+                .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))))
+                .body(Body::empty());
+            match request {
+                Ok(request) => {
+                    let _ = renderer.oneshot(request).await;
+                }
+                Err(err) => tracing::warn!("Cannot regenerate {key}: {err}"),
+            }
+            cache.regenerating().remove(&key);
+        });
+    }
+
+    fn regenerating(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.regenerating
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Cached keys for `path`, with every query string it was cached with.
+    fn keys_for(&self, path: &str) -> Vec<String> {
+        self.pages()
+            .keys()
+            .filter(|key| key.split_once('?').map_or(key.as_str(), |(path, _)| path) == path)
+            .cloned()
+            .collect()
+    }
+
+    /// Follow post changes, see [`PageCache::regenerate_post`].
+    pub fn watch_posts(&self) {
+        let cache = self.clone();
+        app::posts::on_post_changed(move |slug| cache.regenerate_post(slug));
+    }
+
+    /// Render the cached pages a post is shown on again: its own page, the
+    /// blog index and the home page. Visitors get the old copies until the
+    /// new ones are ready.
+    pub fn regenerate_post(&self, slug: &str) {
+        for path in [
+            format!("/blog/{slug}"),
+            "/blog".to_string(),
+            "/".to_string(),
+        ] {
+            for key in self.keys_for(&path) {
+                if self.renderer().is_some() {
+                    self.regenerate(key);
+                } else {
+                    self.pages().remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Middleware answering anonymous `GET` requests for the configured routes
+/// from the [`PageCache`], rendering and storing the page on a miss.
+///
+/// Signed in users may see personalised content, so they always get a fresh
+/// render that is never stored. It runs inside [`crate::auth::authenticate`]
+/// to know about them.
+pub async fn serve_cached(
+    State(cache): State<PageCache>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if !cache.config.enabled() || request.method() != Method::GET || !cache.config.caches(path) {
+        return next.run(request).await;
+    }
+    let regeneration = request.extensions().get::<Regeneration>().is_some();
+    if !regeneration && request.extensions().get::<AuthUser>().is_some() {
+        return with_status(next.run(request).await, CACHE_BYPASS);
+    }
+
+    let key = cache_key(path, request.uri().query());
+    if !regeneration {
+        match cache.lookup(&key) {
+            Lookup::Fresh(page) => {
+                crate::metrics::record_cache("page", true);
+                return with_status(replay(page), CACHE_HIT);
+            }
+            Lookup::Stale(page) => {
+                crate::metrics::record_cache("page", true);
+                cache.regenerate(key);
+                return with_status(replay(page), CACHE_STALE);
+            }
+            Lookup::Missing => crate::metrics::record_cache("page", false),
+        }
+    }
+
+    let response = next.run(request).await;
+    if !is_storable(&response) {
+        // The page is gone or broken now, stop serving the old copy
+        if regeneration {
+            cache.pages().remove(&key);
+        }
+        return with_status(response, CACHE_MISS);
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!("failed to read the response body: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    cache.insert(
+        key,
+        CachedPage {
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored_at: Instant::now(),
+        },
+    );
+    with_status(Response::from_parts(parts, Body::from(body)), CACHE_MISS)
+}
+
+/// The path with only the [`PAGE_PARAMS`] of the query, in a fixed order.
+fn cache_key(path: &str, query: Option<&str>) -> String {
+    let mut params: Vec<&str> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let name = param.split_once('=').map_or(*param, |(name, _)| name);
+            PAGE_PARAMS.contains(&name)
+        })
+        .collect();
+    if params.is_empty() {
+        return path.to_string();
+    }
+    params.sort_unstable();
+    format!("{path}?{}", params.join("&"))
+}
+
+/// Only complete HTML pages that are the same for every visitor.
+fn is_storable(response: &Response) -> bool {
+    let headers = response.headers();
+    let html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    let private = headers
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("no-store") || value.contains("private"));
+    response.status() == StatusCode::OK
+        && html
+        && !private
+        && !headers.contains_key(header::SET_COOKIE)
+}
+
+/// Rebuild a stored page. Its scripts are moved to a fresh CSP nonce, so no
+/// two responses share one.
+fn replay(page: CachedPage) -> Response {
+    let mut headers = page.headers;
+    let nonce_header = HeaderName::from_static(NONCE_HEADER);
+    let body = match headers.get(&nonce_header).map(HeaderValue::as_bytes) {
+        Some(old) if !old.is_empty() => {
+            let nonce = Nonce::new().to_string();
+            let body = replace_all(&page.body, old, nonce.as_bytes());
+            if let Ok(value) = HeaderValue::from_str(&nonce) {
+                headers.insert(nonce_header, value);
+            }
+            Bytes::from(body)
+        }
+        _ => page.body,
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.headers_mut() = headers;
+    response
+}
+
+fn replace_all(haystack: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut replaced = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    while let Some(at) = rest.windows(from.len()).position(|window| window == from) {
+        replaced.extend_from_slice(&rest[..at]);
+        replaced.extend_from_slice(to);
+        rest = &rest[at + from.len()..];
+    }
+    replaced.extend_from_slice(rest);
+    replaced
+}
+
+fn with_status(mut response: Response, status: &'static str) -> Response {
+    response.headers_mut().insert(
+        HeaderName::from_static(CACHE_STATUS_HEADER),
+        HeaderValue::from_static(status),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::response::Html;
+    use axum::routing::get;
+
+    fn cache(max_entries: usize) -> PageCache {
+        PageCache::new(PageCacheConfig {
+            ttl_secs: 60,
+            stale_secs: 60,
+            max_entries,
+            routes: vec!["/".to_string(), "/blog/*".to_string()],
+        })
+    }
+
+    /// A page stored `age` ago.
+    fn page(age: Duration) -> CachedPage {
+        CachedPage {
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"<p>page</p>"),
+            stored_at: Instant::now()
+                .checked_sub(age)
+                .expect("the clock is past the page age"),
+        }
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let cache = cache(10);
+        cache.insert("/fresh".to_string(), page(Duration::from_secs(30)));
+        cache.insert("/stale".to_string(), page(Duration::from_secs(90)));
+        cache.insert("/expired".to_string(), page(Duration::from_secs(150)));
+
+        assert!(matches!(cache.lookup("/fresh"), Lookup::Fresh(_)));
+        // Without a renderer a stale page can't be refreshed, so it is dropped
+        assert!(matches!(cache.lookup("/stale"), Lookup::Missing));
+        assert!(matches!(cache.lookup("/expired"), Lookup::Missing));
+        assert_eq!(cache.pages().len(), 1);
+    }
+
+    #[test]
+    fn test_max_entries_eviction() {
+        let cache = cache(2);
+        cache.insert("/blog/a".to_string(), page(Duration::from_secs(20)));
+        cache.insert("/blog/b".to_string(), page(Duration::from_secs(10)));
+        cache.insert("/blog/c".to_string(), page(Duration::ZERO));
+        assert!(matches!(cache.lookup("/blog/a"), Lookup::Missing));
+        assert!(matches!(cache.lookup("/blog/b"), Lookup::Fresh(_)));
+        assert!(matches!(cache.lookup("/blog/c"), Lookup::Fresh(_)));
+
+        // Pages past their stale window go first
+        cache.insert("/blog/b".to_string(), page(Duration::from_secs(150)));
+        cache.insert("/blog/d".to_string(), page(Duration::ZERO));
+        assert!(matches!(cache.lookup("/blog/c"), Lookup::Fresh(_)));
+        assert!(matches!(cache.lookup("/blog/d"), Lookup::Fresh(_)));
+        assert_eq!(cache.pages().len(), 2);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(cache_key("/blog", None), "/blog");
+        assert_eq!(cache_key("/blog", Some("x=1&utm_source=feed")), "/blog");
+    }
+
+    #[tokio::test]
+    async fn test_cache_status() {
+        let cache = cache(10);
+        let router = Router::new()
+            .route("/", get(|| async { Html("<p>home</p>") }))
+            .layer(axum::middleware::from_fn_with_state(cache, serve_cached));
+        let status = |response: Response| {
+            response
+                .headers()
+                .get(CACHE_STATUS_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let anonymous = |uri: &str| Request::get(uri).body(Body::empty()).expect("request");
+
+        let signed_in = Request::get("/")
+            .extension(AuthUser {
+                id: "user".to_string(),
+                admin: false,
+            })
+            .body(Body::empty())
+            .expect("request");
+        let response = router.clone().oneshot(signed_in).await.expect("response");
+        assert_eq!(status(response), CACHE_BYPASS);
+
+        let response = router
+            .clone()
+            .oneshot(anonymous("/?x=1"))
+            .await
+            .expect("response");
+        assert_eq!(status(response), CACHE_MISS);
+        // Other cookies and unknown parameters are served from the cache
+        let mut request = anonymous("/?x=2");
+        request
+            .headers_mut()
+            .insert(header::COOKIE, HeaderValue::from_static("consent=yes"));
+        let response = router.oneshot(request).await.expect("response");
+        assert_eq!(status(response), CACHE_HIT);
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// Bundles produced by `cargo leptos build` that are worth compressing ahead
+/// of time. Everything else is compressed per response, if at all.
+const EXTENSIONS: [&str; 3] = ["wasm", "js", "css"];
+
+/// What `precompress` wrote.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub files: usize,
+    pub original_bytes: u64,
+    pub brotli_bytes: u64,
+    pub gzip_bytes: u64,
+}
+
+/// Write `.br` and `.gz` siblings next to every bundle under `root`, for
+/// `ServeDir` to pick when the client accepts them. Siblings newer than
+/// their source are left alone.
+pub fn precompress(root: &Path) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+    for path in bundles(root)? {
+        let original_bytes = path.metadata()?.len();
+        let brotli_bytes = write_sibling(&path, "br", |source, target| {
+            let mut writer = brotli::CompressorWriter::new(target, 64 * 1024, 11, 22);
+            io::copy(source, &mut writer)?;
+            writer.flush()
+        })?;
+        let gzip_bytes = write_sibling(&path, "gz", |source, target| {
+            let mut writer = GzEncoder::new(target, Compression::best());
+            io::copy(source, &mut writer)?;
+            writer.finish().map(drop)
+        })?;
+
+        summary.files += 1;
+        summary.original_bytes += original_bytes;
+        summary.brotli_bytes += brotli_bytes;
+        summary.gzip_bytes += gzip_bytes;
+    }
+    Ok(summary)
+}
+
+fn bundles(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| EXTENSIONS.contains(&extension))
+            {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Compress `path` into `<path>.<extension>` unless that file is up to date,
+/// returning the size of the sibling.
+fn write_sibling(
+    path: &Path,
+    extension: &str,
+    compress: impl FnOnce(&mut BufReader<File>, &mut File) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut target_name = path.as_os_str().to_owned();
+    target_name.push(".");
+    target_name.push(extension);
+    let target = PathBuf::from(target_name);
+
+    let modified = path.metadata()?.modified()?;
+    if let Ok(existing) = target.metadata() {
+        if existing.modified()? >= modified {
+            return Ok(existing.len());
+        }
+    }
+
+    // Same partial-then-rename as the image cache, a request never sees a
+    // half written sibling
+    let partial = target.with_extension(format!("{extension}.partial"));
+    let mut source = BufReader::new(File::open(path)?);
+    let mut file = File::create(&partial)?;
+    compress(&mut source, &mut file)?;
+    file.sync_all()?;
+    std::fs::rename(&partial, &target)?;
+    target.metadata().map(|metadata| metadata.len())
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use config::{RateLimitConfig, RateLimitStore};
+use database::connection::{acquire, DbPool};
+use database::rate_limits;
+use tokio::task::JoinHandle;
+
+use crate::auth::AuthUser;
+use crate::runtime::Settings;
+
+/// Budgets are requests per window of this many seconds.
+const WINDOW_SECS: u64 = 60;
+/// Client address set by Fly's edge proxy.
+const FLY_CLIENT_IP: &str = "fly-client-ip";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Request counts by key, for the current window.
+enum Store {
+    Memory(Mutex<HashMap<String, (i64, u32)>>),
+    Postgres(DbPool),
+}
+
+impl Store {
+    /// Count a request for `key` in `window`, returning the requests counted
+    /// in it so far.
+    async fn hit(&self, key: &str, window: i64) -> Result<u32, String> {
+        match self {
+            Store::Memory(counts) => {
+                let mut counts = counts.lock().unwrap_or_else(PoisonError::into_inner);
+                let count = counts.entry(key.to_string()).or_insert((window, 0));
+                if count.0 != window {
+                    *count = (window, 0);
+                }
+                count.1 += 1;
+                Ok(count.1)
+            }
+            Store::Postgres(pool) => {
+                let mut connection = acquire(pool).await.map_err(|err| err.to_string())?;
+                let hits = rate_limits::hit(&mut connection, key, window)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                Ok(u32::try_from(hits).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Forget the windows before `window`.
+    async fn prune(&self, window: i64) -> Result<(), String> {
+        match self {
+            Store::Memory(counts) => {
+                counts
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .retain(|_, (counted, _)| *counted >= window);
+                Ok(())
+            }
+            Store::Postgres(pool) => {
+                let mut connection = acquire(pool).await.map_err(|err| err.to_string())?;
+                rate_limits::prune(&mut connection, window)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+/// Fixed window rate limiting by client, with the budgets from
+/// `[rate_limit]` and `runtime.rate_limit_per_minute`.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    /// Holds the default budget, which can change on reload.
+    settings: Settings,
+    store: Store,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, settings: Settings, pool: DbPool) -> Self {
+        let store = match config.store {
+            RateLimitStore::Memory => Store::Memory(Mutex::default()),
+            RateLimitStore::Postgres => Store::Postgres(pool),
+        };
+        Self {
+            config,
+            settings,
+            store,
+        }
+    }
+
+    /// Drop the counts of past windows once a window.
+    pub fn spawn_pruning(self: &Arc<Self>) -> JoinHandle<()> {
+        let limiter = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(WINDOW_SECS));
+            loop {
+                interval.tick().await;
+                let (window, _) = current_window();
+                if let Err(err) = limiter.store.prune(window).await {
+                    tracing::warn!("Failed to prune rate limit counts: {err}");
+                }
+            }
+        })
+    }
+
+    /// The scope requests are counted in and its budget, `None` when the
+    /// request isn't limited.
+    fn budget(&self, method: &Method, path: &str) -> Option<(String, u32)> {
+        if let Some(rule) = self.config.rule_for(path) {
+            return Some((rule.pattern.clone(), rule.per_minute));
+        }
+        let changes_state = !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        changes_state.then(|| {
+            let per_minute = self.settings.borrow().runtime.rate_limit_per_minute;
+            ("default".to_string(), per_minute)
+        })
+    }
+
+    /// The client's address. Forwarding headers are only believed when the
+    /// connection comes from a trusted proxy, anyone else could forge them.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.config.is_trusted_proxy(peer) {
+            return peer;
+        }
+        let fly_client_ip = headers
+            .get(FLY_CLIENT_IP)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        if let Some(ip) = fly_client_ip {
+            return ip;
+        }
+
+        // Each proxy appends the address it got the request from, so the
+        // client is the last one that isn't ours. Anything before it was
+        // written by the client.
+        let forwarded: Vec<IpAddr> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|addr| addr.trim().parse().ok())
+            .collect();
+        forwarded
+            .into_iter()
+            .rev()
+            .find(|ip| !self.config.is_trusted_proxy(*ip))
+            .unwrap_or(peer)
+    }
+}
+
+/// Current window and the seconds left in it.
+fn current_window() -> (i64, u64) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let window = i64::try_from(now / WINDOW_SECS).unwrap_or(i64::MAX);
+    (window, WINDOW_SECS - now % WINDOW_SECS)
+}
+
+/// Middleware counting requests per client, by user for authenticated
+/// requests and by IP otherwise, and answering `429 Too Many Requests` with
+/// a `Retry-After` once the budget of the window is spent.
+///
+/// If the store can't be reached requests are let through.
+pub async fn limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some((scope, per_minute)) = limiter.budget(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let client = match request.extensions().get::<AuthUser>() {
+        Some(user) => format!("user:{}", user.id),
+        None => format!("ip:{}", limiter.client_ip(peer.ip(), request.headers())),
+    };
+
+    let (window, retry_after) = current_window();
+    let key = format!("{scope}|{client}");
+    match limiter.store.hit(&key, window).await {
+        Ok(hits) if hits > per_minute => {
+            metrics::counter!("rate_limited_requests_total", "scope" => scope).increment(1);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                "Too many requests, try again later",
+            )
+                .into_response()
+        }
+        Ok(_) => next.run(request).await,
+        Err(err) => {
+            tracing::warn!("Rate limit store unavailable, letting the request through: {err}");
+            next.run(request).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use config::RuntimeConfig;
+    use tokio::sync::watch;
+    use tracing_subscriber::filter::LevelFilter;
+
+    use crate::runtime::RuntimeSettings;
+
+    fn limiter(trusted_proxies: &[&str]) -> RateLimiter {
+        let (_, settings) = watch::channel(Arc::new(RuntimeSettings {
+            log_level: LevelFilter::INFO,
+            runtime: RuntimeConfig::default(),
+        }));
+        RateLimiter {
+            config: RateLimitConfig {
+                routes: Vec::new(),
+                trusted_proxies: trusted_proxies
+                    .iter()
+                    .map(|range| range.parse().expect("range"))
+                    .collect(),
+                store: RateLimitStore::Memory,
+            },
+            settings,
+            store: Store::Memory(Mutex::default()),
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().expect("header value"));
+        }
+        headers
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().expect("ip")
+    }
+
+    #[test]
+    fn test_untrusted_peer_headers_are_ignored() {
+        let limiter = limiter(&["172.16.0.0/12"]);
+        let spoofed = headers(&[(FLY_CLIENT_IP, "1.2.3.4"), (X_FORWARDED_FOR, "5.6.7.8")]);
+        assert_eq!(
+            limiter.client_ip(ip("203.0.113.9"), &spoofed),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn test_trusted_proxy_headers() {
+        let limiter = limiter(&["172.16.0.0/12"]);
+        let proxy = ip("172.16.3.4");
+
+        let fly = headers(&[
+            (FLY_CLIENT_IP, "198.51.100.7"),
+            (X_FORWARDED_FOR, "5.6.7.8"),
+        ]);
+        assert_eq!(limiter.client_ip(proxy, &fly), ip("198.51.100.7"));
+
+        // The client wrote the first two entries, our proxies the last one
+        let chain = headers(&[(
+            X_FORWARDED_FOR,
+            "1.1.1.1, 2.2.2.2, 198.51.100.7, 172.16.9.9",
+        )]);
+        assert_eq!(limiter.client_ip(proxy, &chain), ip("198.51.100.7"));
+
+        // Entries spread over several headers are one list
+        let split = headers(&[
+            (X_FORWARDED_FOR, "1.1.1.1"),
+            (X_FORWARDED_FOR, "198.51.100.7"),
+        ]);
+        assert_eq!(limiter.client_ip(proxy, &split), ip("198.51.100.7"));
+
+        // Nothing usable falls back to the peer
+        let garbage = headers(&[(X_FORWARDED_FOR, "unknown, 172.16.9.9")]);
+        assert_eq!(limiter.client_ip(proxy, &garbage), proxy);
+        assert_eq!(limiter.client_ip(proxy, &HeaderMap::new()), proxy);
+    }
+}
@@ -0,0 +1,504 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{post, put},
+    Json, Router,
+};
+use database::connection::{acquire, DbConnection, DbPool};
+use database::models::{NewRedirect, Redirect};
+use database::redirects;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::auth::Admin;
+
+/// How often rules are reloaded, to pick up changes made on other machines.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Never redirected, so a broad rule can't lock admins out of removing it.
+const ADMIN_PREFIX: &str = "/admin";
+
+#[derive(Debug, Error)]
+pub enum RedirectError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("a redirect for this source already exists")]
+    Conflict,
+
+    #[error("database error: {0}")]
+    Database(String),
+}
+
+impl From<DieselError> for RedirectError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => RedirectError::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                RedirectError::Conflict
+            }
+            err => RedirectError::Database(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for RedirectError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            RedirectError::Invalid(_) => StatusCode::BAD_REQUEST,
+            RedirectError::NotFound => StatusCode::NOT_FOUND,
+            RedirectError::Conflict => StatusCode::CONFLICT,
+            RedirectError::Database(_) => {
+                tracing::error!("{self}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// What a rule does with a request.
+#[derive(Debug, PartialEq, Eq)]
+enum Resolution {
+    Redirect(StatusCode, String),
+    Gone,
+}
+
+struct Rule {
+    target: Option<String>,
+    status: StatusCode,
+}
+
+/// The rules, split for lookup: exact sources by path, and `/*` prefixes
+/// longest first so the most specific one wins.
+#[derive(Default)]
+struct Rules {
+    exact: HashMap<String, Rule>,
+    prefixes: Vec<(String, Rule)>,
+}
+
+impl Rules {
+    fn new(redirects: Vec<Redirect>) -> Self {
+        let mut rules = Rules::default();
+        for redirect in redirects {
+            let rule = Rule {
+                target: redirect.target,
+                status: u16::try_from(redirect.status)
+                    .ok()
+                    .and_then(|status| StatusCode::from_u16(status).ok())
+                    .unwrap_or(StatusCode::MOVED_PERMANENTLY),
+            };
+            match redirect.source.strip_suffix('*') {
+                Some(prefix) => rules.prefixes.push((prefix.to_string(), rule)),
+                None => {
+                    rules.exact.insert(redirect.source, rule);
+                }
+            }
+        }
+        rules
+            .prefixes
+            .sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        rules
+    }
+
+    fn resolve(&self, uri: &Uri) -> Option<Resolution> {
+        let path = uri.path();
+        let (rule, rest) = match self.exact.get(path) {
+            Some(rule) => (rule, ""),
+            None => self.prefixes.iter().find_map(|(prefix, rule)| {
+                path.strip_prefix(prefix.as_str()).map(|rest| (rule, rest))
+            })?,
+        };
+        let Some(target) = &rule.target else {
+            return Some(Resolution::Gone);
+        };
+
+        // `/*` targets take whatever the source's `/*` matched
+        let mut location = match target.strip_suffix('*') {
+            Some(prefix) => format!("{prefix}{rest}"),
+            None => target.clone(),
+        };
+        if let (Some(query), false) = (uri.query(), location.contains('?')) {
+            location = format!("{location}?{query}");
+        }
+        Some(Resolution::Redirect(rule.status, location))
+    }
+}
+
+/// Redirect rules from the `redirects` table, kept in memory so requests
+/// never wait on the database for them.
+///
+/// Post renames are loaded along with them. Posts are compiled into the
+/// app, so a rename is recorded in the database, applied to the app by
+/// [`app::posts::set_renamed_posts`] and its old URL redirected.
+#[derive(Clone)]
+pub struct Redirects {
+    pool: DbPool,
+    rules: Arc<RwLock<Arc<Rules>>>,
+}
+
+impl Redirects {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            rules: Arc::default(),
+        }
+    }
+
+    /// Load the rules and post renames again. On failure the current ones
+    /// are kept.
+    pub async fn reload(&self) {
+        let loaded = match acquire(&self.pool).await {
+            Ok(mut connection) => load(&mut connection).await.map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        match loaded {
+            Ok((redirects, renamed)) => {
+                let rules = Arc::new(Rules::new(redirects));
+                *self.rules.write().unwrap_or_else(PoisonError::into_inner) = rules;
+                app::posts::set_renamed_posts(renamed.into_iter().collect());
+            }
+            Err(err) => tracing::warn!("Keeping the current redirects, failed to load them: {err}"),
+        }
+    }
+
+    pub fn spawn_refresh(&self) -> JoinHandle<()> {
+        let redirects = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+            // The first tick fires right away, the rules were just loaded
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                redirects.reload().await;
+            }
+        })
+    }
+
+    fn rules(&self) -> Arc<Rules> {
+        Arc::clone(&self.rules.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Admin API under `/admin`: CRUD for `/admin/redirects` and
+    /// `/admin/posts/{slug}/rename`, which also redirects the old URL.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route("/admin/redirects", post(create).get(list))
+            .route("/admin/redirects/{id}", put(update).delete(delete))
+            .route("/admin/posts/{slug}/rename", post(rename_post))
+            .with_state(self)
+    }
+}
+
+async fn load(
+    connection: &mut DbConnection<'_>,
+) -> Result<(Vec<Redirect>, Vec<(String, String)>), DieselError> {
+    Ok((
+        redirects::list(connection).await?,
+        redirects::renamed_posts(connection).await?,
+    ))
+}
+
+/// Middleware answering requests that match a rule with their redirect, or
+/// `410 Gone`, before they reach a route. The admin API is left alone.
+pub async fn redirect(
+    State(redirects): State<Redirects>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_admin_path(request.uri().path()) {
+        return next.run(request).await;
+    }
+    match redirects.rules().resolve(request.uri()) {
+        Some(Resolution::Redirect(status, location)) => {
+            (status, [(header::LOCATION, location)]).into_response()
+        }
+        Some(Resolution::Gone) => (StatusCode::GONE, "Gone").into_response(),
+        None => next.run(request).await,
+    }
+}
+
+fn is_admin_path(path: &str) -> bool {
+    path.strip_prefix(ADMIN_PREFIX)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Slugs are lowercase words joined by dashes, like the ones posts are
+/// written with.
+fn validate_slug(slug: &str) -> Result<(), RedirectError> {
+    let valid = !slug.is_empty()
+        && slug.len() <= 100
+        && slug.split('-').all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(RedirectError::Invalid(
+            "slug must be lowercase letters and digits joined by dashes".to_string(),
+        ))
+    }
+}
+
+fn validate(redirect: &NewRedirect) -> Result<(), RedirectError> {
+    let invalid = |message: &str| Err(RedirectError::Invalid(message.to_string()));
+    let wildcard = |path: &str| path.strip_suffix("/*").unwrap_or(path).contains('*');
+    if !redirect.source.starts_with('/') {
+        return invalid("source must start with /");
+    }
+    if wildcard(&redirect.source) {
+        return invalid("a source can only end in /*");
+    }
+    if is_admin_path(redirect.source.trim_end_matches('*')) {
+        return invalid("paths under /admin can't be redirected");
+    }
+
+    match (redirect.status, &redirect.target) {
+        (410, None) => Ok(()),
+        (410, Some(_)) => invalid("410 rules have no target"),
+        (301 | 302, None) => invalid("301 and 302 rules need a target"),
+        (301 | 302, Some(target)) => {
+            if !(target.starts_with('/')
+                || target.starts_with("https://")
+                || target.starts_with("http://"))
+            {
+                return invalid("target must be a path or an http(s) URL");
+            }
+            if wildcard(target) || (target.ends_with("/*") && !redirect.source.ends_with("/*")) {
+                return invalid("only a /* source can have a target ending in /*");
+            }
+            if *target == redirect.source {
+                return invalid("a redirect can't point at itself");
+            }
+            Ok(())
+        }
+        _ => invalid("status must be 301, 302 or 410"),
+    }
+}
+
+async fn list(
+    State(redirects): State<Redirects>,
+    _: Admin,
+) -> Result<Json<Vec<Redirect>>, RedirectError> {
+    let mut connection = acquire(&redirects.pool)
+        .await
+        .map_err(|err| RedirectError::Database(err.to_string()))?;
+    Ok(Json(redirects::list(&mut connection).await?))
+}
+
+async fn create(
+    State(redirects): State<Redirects>,
+    Admin(admin): Admin,
+    Json(redirect): Json<NewRedirect>,
+) -> Result<(StatusCode, Json<Redirect>), RedirectError> {
+    validate(&redirect)?;
+    let mut connection = acquire(&redirects.pool)
+        .await
+        .map_err(|err| RedirectError::Database(err.to_string()))?;
+    let created = redirects::create(&mut connection, &redirect).await?;
+    tracing::info!(
+        admin = admin.id,
+        source = %created.source,
+        "redirect created"
+    );
+    redirects.reload().await;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn update(
+    State(redirects): State<Redirects>,
+    Admin(admin): Admin,
+    Path(id): Path<i32>,
+    Json(redirect): Json<NewRedirect>,
+) -> Result<Json<Redirect>, RedirectError> {
+    validate(&redirect)?;
+    let mut connection = acquire(&redirects.pool)
+        .await
+        .map_err(|err| RedirectError::Database(err.to_string()))?;
+    let updated = redirects::update(&mut connection, id, &redirect).await?;
+    tracing::info!(
+        admin = admin.id,
+        source = %updated.source,
+        "redirect updated"
+    );
+    redirects.reload().await;
+    Ok(Json(updated))
+}
+
+async fn delete(
+    State(redirects): State<Redirects>,
+    Admin(admin): Admin,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, RedirectError> {
+    let mut connection = acquire(&redirects.pool)
+        .await
+        .map_err(|err| RedirectError::Database(err.to_string()))?;
+    if !redirects::delete(&mut connection, id).await? {
+        return Err(RedirectError::NotFound);
+    }
+    tracing::info!(admin = admin.id, id, "redirect deleted");
+    redirects.reload().await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct Rename {
+    slug: String,
+}
+
+/// Give a post a new slug. Its old URL, and the redirects that led to it,
+/// move to the new one.
+async fn rename_post(
+    State(redirects): State<Redirects>,
+    Admin(admin): Admin,
+    Path(slug): Path<String>,
+    Json(rename): Json<Rename>,
+) -> Result<StatusCode, RedirectError> {
+    validate_slug(&rename.slug)?;
+    let original = app::posts::original_slug(&slug).ok_or(RedirectError::NotFound)?;
+    if rename.slug == slug {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    if app::posts::get_post(&rename.slug).is_some() {
+        return Err(RedirectError::Conflict);
+    }
+
+    let mut connection = acquire(&redirects.pool)
+        .await
+        .map_err(|err| RedirectError::Database(err.to_string()))?;
+    redirects::rename_post(&mut connection, &original, &slug, &rename.slug).await?;
+    drop(connection);
+    tracing::info!(
+        admin = admin.id,
+        from = %slug,
+        to = %rename.slug,
+        "post renamed"
+    );
+    redirects.reload().await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(source: &str, target: Option<&str>, status: i16) -> Redirect {
+        Redirect {
+            id: 0,
+            source: source.to_string(),
+            target: target.map(str::to_string),
+            status,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+        }
+    }
+
+    fn resolve(rules: &Rules, uri: &str) -> Option<Resolution> {
+        rules.resolve(&uri.parse().expect("uri"))
+    }
+
+    fn moved(location: &str) -> Option<Resolution> {
+        Some(Resolution::Redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            location.to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_resolve() {
+        let rules = Rules::new(vec![
+            rule("/old/*", Some("/new/*"), 301),
+            rule("/old/deep/*", Some("/deeper"), 302),
+            rule("/old/page", Some("/page"), 301),
+            rule("/gone", None, 410),
+            rule("/search", Some("/find?q=all"), 301),
+        ]);
+
+        // Exact sources win over prefixes, the longest prefix over shorter ones
+        assert_eq!(resolve(&rules, "/old/page"), moved("/page"));
+        assert_eq!(
+            resolve(&rules, "/old/deep/x"),
+            Some(Resolution::Redirect(
+                StatusCode::FOUND,
+                "/deeper".to_string()
+            ))
+        );
+        // `/*` targets get what the source's `/*` matched
+        assert_eq!(resolve(&rules, "/old/a/b"), moved("/new/a/b"));
+        // The query is kept unless the target has its own
+        assert_eq!(resolve(&rules, "/old/a?x=1"), moved("/new/a?x=1"));
+        assert_eq!(resolve(&rules, "/search?q=rust"), moved("/find?q=all"));
+        assert_eq!(resolve(&rules, "/gone"), Some(Resolution::Gone));
+        assert_eq!(resolve(&rules, "/gone/child"), None);
+        assert_eq!(resolve(&rules, "/other"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let check = |source: &str, target: Option<&str>, status: i16| {
+            validate(&NewRedirect {
+                source: source.to_string(),
+                target: target.map(str::to_string),
+                status,
+            })
+            .is_ok()
+        };
+
+        assert!(check("/old", Some("/new"), 301));
+        assert!(check("/old/*", Some("/new/*"), 302));
+        assert!(check("/old", Some("https://example.com/"), 301));
+        assert!(check("/gone", None, 410));
+        assert!(check("/*", Some("https://example.com/*"), 301));
+
+        assert!(!check("old", Some("/new"), 301));
+        assert!(!check("/old*", Some("/new"), 301));
+        assert!(!check("/a/*/b", Some("/new"), 301));
+        assert!(!check("/old", Some("/new/*"), 301));
+        assert!(!check("/old", Some("ftp://example.com"), 301));
+        assert!(!check("/old", Some("/old"), 301));
+        assert!(!check("/old", None, 301));
+        assert!(!check("/gone", Some("/new"), 410));
+        assert!(!check("/old", Some("/new"), 307));
+        assert!(!check("/admin/redirects", Some("/new"), 301));
+        assert!(!check("/admin/*", None, 410));
+        assert!(!check("/admin", Some("/new"), 301));
+    }
+
+    #[test]
+    fn test_admin_paths() {
+        assert!(is_admin_path("/admin"));
+        assert!(is_admin_path("/admin/redirects"));
+        assert!(!is_admin_path("/administrator"));
+        assert!(!is_admin_path("/blog/admin"));
+    }
+
+    #[test]
+    fn test_validate_slug() {
+        assert!(validate_slug("custom-fields-2").is_ok());
+        for slug in [
+            "",
+            "Custom",
+            "two--dashes",
+            "-leading",
+            "trailing-",
+            "a/b",
+            "a b",
+        ] {
+            assert!(validate_slug(slug).is_err(), "{slug}");
+        }
+    }
+}
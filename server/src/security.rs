@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use app::csp::NONCE_HEADER;
+use app::rendering::directives::Embed;
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::post,
+    Router,
+};
+use leptos::prelude::LeptosOptions;
+use serde_json::Value;
+
+/// Path browsers send CSP violation reports to.
+pub const REPORT_PATH: &str = "/csp-report";
+/// Reports are small, anything bigger is not worth reading.
+const REPORT_LIMIT: usize = 16 * 1024;
+
+const STATIC_HEADERS: [(HeaderName, &str); 5] = [
+    (
+        header::STRICT_TRANSPORT_SECURITY,
+        "max-age=63072000; includeSubDomains",
+    ),
+    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (header::X_FRAME_OPTIONS, "DENY"),
+    (header::REFERRER_POLICY, "strict-origin-when-cross-origin"),
+    (
+        HeaderName::from_static("permissions-policy"),
+        "camera=(), microphone=(), geolocation=(), payment=(), usb=()",
+    ),
+];
+
+/// Security headers added to every response, and the Content-Security-Policy
+/// added to HTML pages.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    report_only: bool,
+    /// Port of the `cargo leptos watch` reload socket, when it is running.
+    reload_port: Option<u32>,
+}
+
+impl SecurityHeaders {
+    pub fn new(report_only: bool, options: &LeptosOptions) -> Self {
+        Self {
+            report_only,
+            // Same check `AutoReload` uses before rendering its script
+            reload_port: std::env::var_os("LEPTOS_WATCH").map(|_| options.reload_port),
+        }
+    }
+
+    fn policy_header(&self) -> HeaderName {
+        if self.report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        }
+    }
+
+    /// Scripts only run when they carry the nonce of the render, scripts
+    /// they load are trusted through `'strict-dynamic'`. Pages rendered
+    /// without a nonce can only load scripts from this origin.
+    fn policy(&self, nonce: Option<&str>) -> String {
+        let scripts = match nonce {
+            Some(nonce) => format!("'nonce-{nonce}' 'strict-dynamic' 'wasm-unsafe-eval'"),
+            None => "'self' 'wasm-unsafe-eval'".to_string(),
+        };
+        let connect = match self.reload_port {
+            Some(port) => format!("'self' ws://*:{port} wss://*:{port}"),
+            None => "'self'".to_string(),
+        };
+        let frames = Embed::ALLOWED_HOSTS
+            .iter()
+            .map(|host| host.trim_end_matches('/'))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        [
+            "default-src 'self'".to_string(),
+            format!("script-src {scripts}"),
+            // Components still set inline `style` attributes
+            "style-src 'self' 'unsafe-inline'".to_string(),
+            "img-src 'self' data:".to_string(),
+            format!("connect-src {connect}"),
+            format!("frame-src {frames}"),
+            "object-src 'none'".to_string(),
+            "base-uri 'none'".to_string(),
+            "form-action 'self'".to_string(),
+            "frame-ancestors 'none'".to_string(),
+            format!("report-uri {REPORT_PATH}"),
+        ]
+        .join("; ")
+    }
+
+    /// Router accepting violation reports at `/csp-report`.
+    pub fn router<S>() -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Router::new()
+            .route(REPORT_PATH, post(report_handler))
+            .layer(DefaultBodyLimit::max(REPORT_LIMIT))
+    }
+}
+
+/// Middleware adding the headers, with the policy built from the nonce the
+/// page rendered with.
+pub async fn add_headers(
+    State(security): State<Arc<SecurityHeaders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    let nonce = headers.remove(NONCE_HEADER);
+    for (name, value) in STATIC_HEADERS {
+        headers.insert(name, HeaderValue::from_static(value));
+    }
+    if is_html(headers) {
+        let policy = security.policy(nonce.as_ref().and_then(|nonce| nonce.to_str().ok()));
+        if let Ok(policy) = HeaderValue::from_str(&policy) {
+            headers.insert(security.policy_header(), policy);
+        }
+    }
+    response
+}
+
+fn is_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"))
+}
+
+/// Log violation reports, either the `report-uri` format or a batch from the
+/// Reporting API.
+async fn report_handler(body: Bytes) -> StatusCode {
+    let Ok(report) = serde_json::from_slice::<Value>(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let violations = match report {
+        Value::Object(mut object) => object.remove("csp-report").into_iter().collect(),
+        Value::Array(reports) => reports
+            .into_iter()
+            .filter_map(|mut report| report.get_mut("body").map(Value::take))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for violation in violations {
+        let field = |legacy: &str, current: &str| {
+            violation
+                .get(legacy)
+                .or_else(|| violation.get(current))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let directive = field("violated-directive", "effectiveDirective");
+        let blocked = field("blocked-uri", "blockedURL");
+        let document = field("document-uri", "documentURL");
+        metrics::counter!("csp_violations_total").increment(1);
+        tracing::warn!(
+            directive,
+            blocked,
+            document,
+            "content security policy violation"
+        );
+    }
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use app::{shell, App};
+    use axum::body::{to_bytes, Body};
+    use axum::middleware;
+    use leptos_axum::{generate_route_list, LeptosRoutes};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_script_nonce_matches_policy() {
+        let options = LeptosOptions::builder().output_name("test").build();
+        let security = SecurityHeaders::new(false, &options);
+        let router = Router::new()
+            .leptos_routes(&options, generate_route_list(App), {
+                let options = options.clone();
+                move || shell(options.clone())
+            })
+            .layer(middleware::from_fn_with_state(
+                Arc::new(security),
+                add_headers,
+            ))
+            .with_state(options);
+
+        let request = axum::http::Request::get("/")
+            .body(Body::empty())
+            .expect("request");
+        let response = router.oneshot(request).await.expect("response");
+        let policy = response
+            .headers()
+            .get(header::CONTENT_SECURITY_POLICY)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        assert!(response.headers().get(NONCE_HEADER).is_none());
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let html = String::from_utf8_lossy(&body);
+
+        let nonces: Vec<&str> = html
+            .split("nonce=\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .collect();
+        assert!(!nonces.is_empty(), "no script carries a nonce");
+        for nonce in nonces {
+            assert!(
+                policy.contains(&format!("'nonce-{nonce}'")),
+                "{nonce} is not allowed by {policy}"
+            );
+        }
+    }
+}
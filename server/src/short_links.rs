@@ -0,0 +1,427 @@
+use std::collections::{HashMap, HashSet};
+
+use app::posts::{get_post, get_posts};
+use app::site::{absolute_url, SITE_URL};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use database::connection::{acquire, DbPool};
+use database::models::{NewShortLink, ShortLink, ShortLinkClicks};
+use database::short_links;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use qrcode::{render::svg, QrCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::auth::Admin;
+
+const CODE_LEN: usize = 6;
+/// Characters of generated codes, without the ones easily mistaken for each
+/// other when typed from print (0/O, 1/l/I).
+const CODE_ALPHABET: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+/// Generated codes tried for a post before giving up on collisions.
+const CODE_ATTEMPTS: u32 = 8;
+const QR_SIZE: u32 = 256;
+/// Referrer hosts counted by name, with their subdomains. Any other is
+/// counted as `other`, since the `Referer` is whatever the client sends.
+const KNOWN_REFERRERS: &[&str] = &[
+    "bing.com",
+    "duckduckgo.com",
+    "facebook.com",
+    "github.com",
+    "google.com",
+    "linkedin.com",
+    "lnkd.in",
+    "mastodon.social",
+    "news.ycombinator.com",
+    "reddit.com",
+    "t.co",
+    "twitter.com",
+    "x.com",
+];
+/// Longest host name DNS allows, anything longer is made up.
+const MAX_HOST_LEN: usize = 253;
+
+#[derive(Debug, Error)]
+pub enum ShortLinkError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("this code is taken")]
+    Conflict,
+
+    #[error("database error: {0}")]
+    Database(String),
+
+    #[error("failed to render QR code: {0}")]
+    Render(String),
+}
+
+impl From<DieselError> for ShortLinkError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => ShortLinkError::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ShortLinkError::Conflict
+            }
+            err => ShortLinkError::Database(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ShortLinkError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ShortLinkError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ShortLinkError::NotFound => StatusCode::NOT_FOUND,
+            ShortLinkError::Conflict => StatusCode::CONFLICT,
+            ShortLinkError::Database(_) | ShortLinkError::Render(_) => {
+                tracing::error!("{self}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Short links `/s/{code}` to blog posts, with click counts per day and
+/// referrer and a QR code for each.
+#[derive(Clone)]
+pub struct ShortLinks {
+    pool: DbPool,
+}
+
+impl ShortLinks {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Give every post without one a generated code, in the background.
+    pub fn spawn_generate_missing(&self) -> JoinHandle<()> {
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            if let Err(err) = generate_missing(&pool).await {
+                tracing::warn!("Failed to generate short links: {err}");
+            }
+        })
+    }
+
+    /// Router serving `/s/{code}` and `/s/{code}/qr.svg`, and the admin API
+    /// under `/admin/short-links`.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new()
+            .route("/s/{code}", get(follow))
+            .route("/s/{code}/qr.svg", get(qr_code))
+            .route("/admin/short-links", get(list).post(create))
+            .route("/admin/short-links/{id}", delete(remove))
+            .route("/admin/short-links/{id}/clicks", get(clicks))
+            .with_state(self)
+    }
+}
+
+/// Code generated for `slug`. The same slug always gets the same codes, a
+/// new `attempt` is only needed on a collision.
+fn generated_code(slug: &str, attempt: u32) -> String {
+    let digest = Sha256::new()
+        .chain_update(slug)
+        .chain_update(attempt.to_be_bytes())
+        .finalize();
+    digest
+        .iter()
+        .take(CODE_LEN)
+        .map(|byte| char::from(CODE_ALPHABET[usize::from(*byte) % CODE_ALPHABET.len()]))
+        .collect()
+}
+
+async fn generate_missing(pool: &DbPool) -> Result<(), ShortLinkError> {
+    let mut connection = acquire(pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    let generated: HashSet<String> = short_links::generated_slugs(&mut connection)
+        .await?
+        .into_iter()
+        .collect();
+
+    for post in get_posts() {
+        if generated.contains(&post.slug) {
+            continue;
+        }
+        let mut created = false;
+        for attempt in 0..CODE_ATTEMPTS {
+            let link = NewShortLink {
+                code: generated_code(&post.slug, attempt),
+                post_slug: post.slug.clone(),
+                vanity: false,
+            };
+            match short_links::create(&mut connection, &link).await {
+                Ok(link) => {
+                    tracing::info!(code = %link.code, slug = %post.slug, "short link generated");
+                    created = true;
+                    break;
+                }
+                Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if !created {
+            tracing::warn!(slug = %post.slug, "no free short link code after {CODE_ATTEMPTS} attempts");
+        }
+    }
+    Ok(())
+}
+
+/// Known host of the `Referer` or its site, `other` for the rest and empty
+/// when there is none.
+fn referrer_host(headers: &HeaderMap) -> String {
+    let Some(referrer) = headers.get(header::REFERER) else {
+        return String::new();
+    };
+    let host = referrer
+        .to_str()
+        .ok()
+        .and_then(|referrer| referrer.split_once("://"))
+        .and_then(|(_, rest)| rest.split(['/', '?', '#', ':']).next())
+        .filter(|host| host.len() <= MAX_HOST_LEN)
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let site = SITE_URL
+        .split_once("://")
+        .map_or(SITE_URL, |(_, host)| host);
+    KNOWN_REFERRERS
+        .iter()
+        .chain([&site])
+        .find(|known| {
+            host == **known
+                || host
+                    .strip_suffix(**known)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+        .map_or_else(|| "other".to_string(), |known| known.to_string())
+}
+
+fn short_url(code: &str) -> String {
+    absolute_url(&format!("/s/{code}"))
+}
+
+/// Send the visitor to the post with a `302`, so browsers come back through
+/// the short link, and count the click.
+async fn follow(
+    State(links): State<ShortLinks>,
+    Path(code): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, ShortLinkError> {
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    let link = short_links::find(&mut connection, &code)
+        .await?
+        .ok_or(ShortLinkError::NotFound)?;
+    drop(connection);
+
+    // Link previews only look, they don't click
+    if method == Method::GET {
+        let referrer = referrer_host(&headers);
+        let pool = links.pool.clone();
+        let id = link.id;
+        tokio::spawn(async move {
+            let recorded = match acquire(&pool).await {
+                Ok(mut connection) => short_links::record_click(&mut connection, id, &referrer)
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            if let Err(err) = recorded {
+                tracing::warn!("Failed to record a short link click: {err}");
+            }
+        });
+    }
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (
+                header::LOCATION,
+                absolute_url(&format!("/blog/{}", link.post_slug)),
+            ),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response())
+}
+
+async fn qr_code(
+    State(links): State<ShortLinks>,
+    Path(code): Path<String>,
+) -> Result<Response, ShortLinkError> {
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    let link = short_links::find(&mut connection, &code)
+        .await?
+        .ok_or(ShortLinkError::NotFound)?;
+
+    let qr = QrCode::new(short_url(&link.code))
+        .map_err(|err| ShortLinkError::Render(err.to_string()))?;
+    let image = qr
+        .render::<svg::Color>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        image,
+    )
+        .into_response())
+}
+
+#[derive(Serialize)]
+struct ShortLinkSummary {
+    #[serde(flatten)]
+    link: ShortLink,
+    url: String,
+    clicks: i64,
+}
+
+async fn list(
+    State(links): State<ShortLinks>,
+    _: Admin,
+) -> Result<Json<Vec<ShortLinkSummary>>, ShortLinkError> {
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    let totals: HashMap<i32, i64> = short_links::total_clicks(&mut connection)
+        .await?
+        .into_iter()
+        .collect();
+    let summaries = short_links::list(&mut connection)
+        .await?
+        .into_iter()
+        .map(|link| ShortLinkSummary {
+            url: short_url(&link.code),
+            clicks: totals.get(&link.id).copied().unwrap_or_default(),
+            link,
+        })
+        .collect();
+    Ok(Json(summaries))
+}
+
+#[derive(Deserialize)]
+struct VanityLink {
+    code: String,
+    slug: String,
+}
+
+/// Add a hand picked code for a post.
+async fn create(
+    State(links): State<ShortLinks>,
+    Admin(admin): Admin,
+    Json(vanity): Json<VanityLink>,
+) -> Result<(StatusCode, Json<ShortLink>), ShortLinkError> {
+    let valid = (3..=32).contains(&vanity.code.len())
+        && vanity
+            .code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ShortLinkError::Invalid(
+            "code must be 3 to 32 letters, digits, dashes or underscores".to_string(),
+        ));
+    }
+    if get_post(&vanity.slug).is_none() {
+        return Err(ShortLinkError::Invalid(format!(
+            "there is no post {}",
+            vanity.slug
+        )));
+    }
+
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    let link = NewShortLink {
+        code: vanity.code,
+        post_slug: vanity.slug,
+        vanity: true,
+    };
+    let created = short_links::create(&mut connection, &link).await?;
+    tracing::info!(admin = %admin.id, code = %created.code, "short link created");
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn remove(
+    State(links): State<ShortLinks>,
+    Admin(admin): Admin,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ShortLinkError> {
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    if !short_links::delete(&mut connection, id).await? {
+        return Err(ShortLinkError::NotFound);
+    }
+    tracing::info!(admin = %admin.id, id, "short link deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn clicks(
+    State(links): State<ShortLinks>,
+    _: Admin,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<ShortLinkClicks>>, ShortLinkError> {
+    let mut connection = acquire(&links.pool)
+        .await
+        .map_err(|err| ShortLinkError::Database(err.to_string()))?;
+    Ok(Json(short_links::clicks(&mut connection, id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::HeaderValue;
+
+    fn referrer(value: &'static str) -> String {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::REFERER, HeaderValue::from_static(value));
+        referrer_host(&headers)
+    }
+
+    #[test]
+    fn test_referrer_host() {
+        assert_eq!(referrer_host(&HeaderMap::new()), "");
+        assert_eq!(
+            referrer("https://www.google.com/search?q=rust"),
+            "google.com"
+        );
+        assert_eq!(
+            referrer("https://news.ycombinator.com/item?id=1"),
+            "news.ycombinator.com"
+        );
+        assert_eq!(
+            referrer("https://fransramirez.com:443/blog"),
+            "fransramirez.com"
+        );
+        assert_eq!(referrer("https://notgoogle.com/"), "other");
+        assert_eq!(referrer("https://google.com.evil.example/"), "other");
+        assert_eq!(referrer("not a url"), "other");
+        let long = format!("https://{}.com/", "a".repeat(300));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::REFERER,
+            HeaderValue::from_str(&long).expect("ascii"),
+        );
+        assert_eq!(referrer_host(&headers), "other");
+    }
+}
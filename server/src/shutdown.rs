@@ -0,0 +1,78 @@
+use std::future::IntoFuture;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::Router;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+/// Serve `app` until `SIGTERM` or Ctrl-C. The listener is closed right away,
+/// in-flight requests get up to `drain_timeout` to finish before we give up
+/// on them.
+pub async fn serve(listener: TcpListener, app: Router, drain_timeout: Duration) -> io::Result<()> {
+    let (draining_tx, draining_rx) = oneshot::channel();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        signal().await;
+        let _ = draining_tx.send(());
+    })
+    .into_future();
+    tokio::pin!(server);
+
+    let deadline = async move {
+        // Only start counting once shutdown begins, never if the server
+        // stops on its own
+        if draining_rx.await.is_err() {
+            return std::future::pending().await;
+        }
+        tracing::info!(
+            "Shutting down, draining connections for up to {}s",
+            drain_timeout.as_secs()
+        );
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        result = &mut server => result,
+        _ = deadline => {
+            tracing::warn!("Drain timeout reached, dropping the remaining connections");
+            Ok(())
+        }
+    }
+}
+
+/// Resolves on the first shutdown signal.
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("Cannot listen for Ctrl-C: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("Cannot listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Ctrl-C received"),
+        _ = terminate => tracing::info!("SIGTERM received"),
+    }
+}
@@ -0,0 +1,40 @@
+use axum::extract::FromRef;
+use database::connection::DbPool;
+use leptos::prelude::LeptosOptions;
+
+use crate::page_cache::PageCache;
+use crate::runtime::Settings;
+
+/// Shared state of the axum router. Leptos handlers pull the
+/// `LeptosOptions` out of it through `FromRef`.
+#[derive(Clone)]
+pub struct AppState {
+    pub leptos_options: LeptosOptions,
+    pub pool: DbPool,
+    pub settings: Settings,
+    pub page_cache: PageCache,
+}
+
+impl FromRef<AppState> for LeptosOptions {
+    fn from_ref(state: &AppState) -> Self {
+        state.leptos_options.clone()
+    }
+}
+
+impl FromRef<AppState> for DbPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Settings {
+    fn from_ref(state: &AppState) -> Self {
+        state.settings.clone()
+    }
+}
+
+impl FromRef<AppState> for PageCache {
+    fn from_ref(state: &AppState) -> Self {
+        state.page_cache.clone()
+    }
+}